@@ -0,0 +1,40 @@
+//! Compares formatting many codes with `format_codes_into` against calling
+//! `Error::message` for each one.
+//!
+//! Run with `cargo bench`. This uses a plain timing loop rather than a
+//! benchmark framework, so treat the numbers as rough.
+
+use std::time::{Duration, Instant};
+use winerr::{format_codes_into, Error};
+
+const ROUNDS: u32 = 20;
+
+fn time(mut f: impl FnMut()) -> Duration {
+    // One untimed round to warm up.
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let codes: Vec<u32> = (0..16000).collect();
+    let mut out = Vec::with_capacity(codes.len());
+    let bulk = time(|| {
+        out.clear();
+        format_codes_into(&codes, &mut out);
+    });
+    let mut found = 0;
+    let single = time(|| {
+        found = codes
+            .iter()
+            .filter(|&&code| Error::with_code(code).message().is_some())
+            .count();
+    });
+    assert_eq!(out.iter().filter(|m| m.is_some()).count(), found);
+    println!("{} codes, {} with a message", codes.len(), found);
+    println!("format_codes_into: {:?} per round", bulk);
+    println!("Error::message:    {:?} per round", single);
+}
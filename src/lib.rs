@@ -19,145 +19,3706 @@
 //! let error = Error::with_code(0);
 //! assert_eq!(0, error.code());
 //! ```
-//! 
-#![cfg(windows)]
+//!
+//! On platforms other than Windows the crate still builds, so shared code that
+//! mentions [`Error`] type-checks everywhere. There, nothing ever formats and
+//! [`last_error()`] always returns code 0.
 #![warn(missing_docs)]
 
-use std::fmt::{self, Debug, Display, Formatter};
+#[cfg(feature = "cache")]
+use std::collections::BTreeMap;
+#[cfg(feature = "intern")]
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Display};
+use std::io;
 use std::mem::MaybeUninit;
+use std::num::NonZeroU32;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 
-use winapi::{
-    shared::{
-        ntdef::NULL,
-        winerror::HRESULT_CODE
-    },
-    um::{
-        errhandlingapi::GetLastError,
-        winbase::{FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS},
-    },
+mod sys;
+
+use crate::sys::{
+    FormatMessageW, FreeLibrary, GetLastError, GetModuleHandleW, GetUserDefaultUILanguage,
+    LoadLibraryExW, LocalFree, RtlGetVersion, SetLastError, BOOL, ERROR_ACCESS_DENIED,
+    ERROR_ACCOUNT_DISABLED, ERROR_ACCOUNT_LOCKED_OUT, ERROR_ALREADY_EXISTS, ERROR_ARENA_TRASHED,
+    ERROR_BAD_UNIT, ERROR_BROKEN_PIPE, ERROR_BUSY, ERROR_CALL_NOT_IMPLEMENTED, ERROR_CANCELLED,
+    ERROR_CRC, ERROR_DEV_NOT_EXIST, ERROR_DISK_FULL, ERROR_DISK_QUOTA_EXCEEDED,
+    ERROR_ELEVATION_REQUIRED, ERROR_FILE_EXISTS, ERROR_FILE_NOT_FOUND, ERROR_GEN_FAILURE,
+    ERROR_HANDLE_DISK_FULL, ERROR_INSUFFICIENT_BUFFER, ERROR_INVALID_FUNCTION,
+    ERROR_INVALID_HANDLE, ERROR_INVALID_PARAMETER, ERROR_INVALID_TARGET_HANDLE, ERROR_IO_DEVICE,
+    ERROR_IO_PENDING, ERROR_LOCK_VIOLATION, ERROR_LOGON_FAILURE, ERROR_MEDIA_CHANGED,
+    ERROR_MORE_DATA, ERROR_NOT_ENOUGH_MEMORY, ERROR_NOT_ENOUGH_QUOTA, ERROR_NOT_READY,
+    ERROR_NOT_SUPPORTED, ERROR_NO_DATA, ERROR_NO_MEDIA_IN_DRIVE, ERROR_NO_SUCH_USER,
+    ERROR_OPERATION_ABORTED, ERROR_OUTOFMEMORY, ERROR_PASSWORD_EXPIRED, ERROR_PATH_NOT_FOUND,
+    ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, ERROR_PIPE_NOT_CONNECTED, ERROR_PRIVILEGE_NOT_HELD,
+    ERROR_READ_FAULT, ERROR_SECTOR_NOT_FOUND, ERROR_SEM_TIMEOUT, ERROR_SHARING_VIOLATION,
+    ERROR_TIMEOUT, ERROR_TOO_MANY_OPEN_FILES, ERROR_WRITE_FAULT, ERROR_WRITE_PROTECT,
+    FORMAT_MESSAGE_ALLOCATE_BUFFER, FORMAT_MESSAGE_ARGUMENT_ARRAY, FORMAT_MESSAGE_FROM_HMODULE,
+    FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS, FORMAT_MESSAGE_MAX_WIDTH_MASK,
+    HANDLE, HMODULE, INVALID_HANDLE_VALUE, LOAD_LIBRARY_AS_DATAFILE, LPCVOID, NULL, OSVERSIONINFOW,
+    WAIT_ABANDONED, WAIT_FAILED, WAIT_OBJECT_0, WAIT_TIMEOUT, WSAEACCES, WSAEADDRINUSE,
+    WSAEADDRNOTAVAIL, WSAECONNABORTED, WSAECONNREFUSED, WSAECONNRESET, WSAENOTCONN, WSAETIMEDOUT,
+    WSAEWOULDBLOCK,
 };
 
 /// A Windows API Error
-#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+///
+/// The default value is [`Error::success()`].
+#[derive(Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Error {
     code: u32,
 }
+
+// Error is meant to stay a tiny Copy value. Anything richer belongs in a separate type.
+const _: () = assert!(std::mem::size_of::<Error>() == 4);
+const _: () = {
+    const fn assert_copy<T: Copy>() {}
+    assert_copy::<Error>()
+};
+const HRESULT_SEVERITY_BIT: u32 = 0x8000_0000;
+const HRESULT_RESERVED_BIT: u32 = 0x4000_0000;
+// Wait functions report which of up to this many handles was signaled or
+// abandoned by adding its index to WAIT_OBJECT_0 or WAIT_ABANDONED.
+const MAXIMUM_WAIT_OBJECTS: u32 = 64;
+const WAIT_IO_COMPLETION: u32 = 0xC0;
+const FACILITY_RPC: u32 = 1;
+const FACILITY_DISPATCH: u32 = 2;
+const FACILITY_ITF: u32 = 4;
+const FACILITY_WIN32: u32 = 7;
+
+// Curated symbolic names for common codes, sorted by code.
+const SYMBOLS: &[(u32, &str)] = &[
+    (0, "ERROR_SUCCESS"),
+    (1, "ERROR_INVALID_FUNCTION"),
+    (2, "ERROR_FILE_NOT_FOUND"),
+    (3, "ERROR_PATH_NOT_FOUND"),
+    (4, "ERROR_TOO_MANY_OPEN_FILES"),
+    (5, "ERROR_ACCESS_DENIED"),
+    (6, "ERROR_INVALID_HANDLE"),
+    (7, "ERROR_ARENA_TRASHED"),
+    (8, "ERROR_NOT_ENOUGH_MEMORY"),
+    (13, "ERROR_INVALID_DATA"),
+    (14, "ERROR_OUTOFMEMORY"),
+    (15, "ERROR_INVALID_DRIVE"),
+    (18, "ERROR_NO_MORE_FILES"),
+    (19, "ERROR_WRITE_PROTECT"),
+    (20, "ERROR_BAD_UNIT"),
+    (21, "ERROR_NOT_READY"),
+    (23, "ERROR_CRC"),
+    (31, "ERROR_GEN_FAILURE"),
+    (32, "ERROR_SHARING_VIOLATION"),
+    (33, "ERROR_LOCK_VIOLATION"),
+    (38, "ERROR_HANDLE_EOF"),
+    (39, "ERROR_HANDLE_DISK_FULL"),
+    (50, "ERROR_NOT_SUPPORTED"),
+    (53, "ERROR_BAD_NETPATH"),
+    (55, "ERROR_DEV_NOT_EXIST"),
+    (64, "ERROR_NETNAME_DELETED"),
+    (80, "ERROR_FILE_EXISTS"),
+    (86, "ERROR_INVALID_PASSWORD"),
+    (87, "ERROR_INVALID_PARAMETER"),
+    (109, "ERROR_BROKEN_PIPE"),
+    (110, "ERROR_OPEN_FAILED"),
+    (111, "ERROR_BUFFER_OVERFLOW"),
+    (112, "ERROR_DISK_FULL"),
+    (120, "ERROR_CALL_NOT_IMPLEMENTED"),
+    (121, "ERROR_SEM_TIMEOUT"),
+    (122, "ERROR_INSUFFICIENT_BUFFER"),
+    (123, "ERROR_INVALID_NAME"),
+    (126, "ERROR_MOD_NOT_FOUND"),
+    (127, "ERROR_PROC_NOT_FOUND"),
+    (131, "ERROR_NEGATIVE_SEEK"),
+    (145, "ERROR_DIR_NOT_EMPTY"),
+    (170, "ERROR_BUSY"),
+    (183, "ERROR_ALREADY_EXISTS"),
+    (193, "ERROR_BAD_EXE_FORMAT"),
+    (203, "ERROR_ENVVAR_NOT_FOUND"),
+    (206, "ERROR_FILENAME_EXCED_RANGE"),
+    (231, "ERROR_PIPE_BUSY"),
+    (232, "ERROR_NO_DATA"),
+    (233, "ERROR_PIPE_NOT_CONNECTED"),
+    (234, "ERROR_MORE_DATA"),
+    (258, "WAIT_TIMEOUT"),
+    (259, "ERROR_NO_MORE_ITEMS"),
+    (267, "ERROR_DIRECTORY"),
+    (288, "ERROR_NOT_OWNER"),
+    (299, "ERROR_PARTIAL_COPY"),
+    (317, "ERROR_MR_MID_NOT_FOUND"),
+    (487, "ERROR_INVALID_ADDRESS"),
+    (534, "ERROR_ARITHMETIC_OVERFLOW"),
+    (535, "ERROR_PIPE_CONNECTED"),
+    (536, "ERROR_PIPE_LISTENING"),
+    (676, "ERROR_HANDLES_CLOSED"),
+    (735, "ERROR_ABANDONED_WAIT_0"),
+    (740, "ERROR_ELEVATION_REQUIRED"),
+    (995, "ERROR_OPERATION_ABORTED"),
+    (996, "ERROR_IO_INCOMPLETE"),
+    (997, "ERROR_IO_PENDING"),
+    (998, "ERROR_NOACCESS"),
+    (1001, "ERROR_STACK_OVERFLOW"),
+    (1004, "ERROR_INVALID_FLAGS"),
+    (1056, "ERROR_SERVICE_ALREADY_RUNNING"),
+    (1058, "ERROR_SERVICE_DISABLED"),
+    (1060, "ERROR_SERVICE_DOES_NOT_EXIST"),
+    (1062, "ERROR_SERVICE_NOT_ACTIVE"),
+    (1112, "ERROR_NO_MEDIA_IN_DRIVE"),
+    (1114, "ERROR_DLL_INIT_FAILED"),
+    (1167, "ERROR_DEVICE_NOT_CONNECTED"),
+    (1168, "ERROR_NOT_FOUND"),
+    (1223, "ERROR_CANCELLED"),
+    (1225, "ERROR_CONNECTION_REFUSED"),
+    (1236, "ERROR_CONNECTION_ABORTED"),
+    (1237, "ERROR_RETRY"),
+    (1300, "ERROR_NOT_ALL_ASSIGNED"),
+    (1307, "ERROR_INVALID_OWNER"),
+    (1313, "ERROR_NO_SUCH_PRIVILEGE"),
+    (1314, "ERROR_PRIVILEGE_NOT_HELD"),
+    (1317, "ERROR_NO_SUCH_USER"),
+    (1326, "ERROR_LOGON_FAILURE"),
+    (1327, "ERROR_ACCOUNT_RESTRICTION"),
+    (1330, "ERROR_PASSWORD_EXPIRED"),
+    (1331, "ERROR_ACCOUNT_DISABLED"),
+    (1346, "ERROR_BAD_IMPERSONATION_LEVEL"),
+    (1359, "ERROR_INTERNAL_ERROR"),
+    (1400, "ERROR_INVALID_WINDOW_HANDLE"),
+    (1450, "ERROR_NO_SYSTEM_RESOURCES"),
+    (1460, "ERROR_TIMEOUT"),
+    (1617, "ERROR_DEVICE_REMOVED"),
+    (1785, "ERROR_UNRECOGNIZED_MEDIA"),
+    (1815, "ERROR_RESOURCE_LANG_NOT_FOUND"),
+    (1816, "ERROR_NOT_ENOUGH_QUOTA"),
+    (1909, "ERROR_ACCOUNT_LOCKED_OUT"),
+    (2404, "ERROR_DEVICE_IN_USE"),
+    (10004, "WSAEINTR"),
+    (10013, "WSAEACCES"),
+    (10035, "WSAEWOULDBLOCK"),
+    (10048, "WSAEADDRINUSE"),
+    (10050, "WSAENETDOWN"),
+    (10053, "WSAECONNABORTED"),
+    (10054, "WSAECONNRESET"),
+    (10060, "WSAETIMEDOUT"),
+    (10061, "WSAECONNREFUSED"),
+    (10093, "WSANOTINITIALISED"),
+    (11001, "WSAHOST_NOT_FOUND"),
+];
+
+/// Returns the code for a winerror.h name such as `"ERROR_ACCESS_DENIED"`. This
+/// is the reverse of [`Error::symbol_name()`] and knows the same curated set.
+pub fn code_from_symbol(name: &str) -> Option<u32> {
+    SYMBOLS
+        .iter()
+        .find(|&&(_, symbol)| symbol == name)
+        .map(|&(code, _)| code)
+}
+
+/// The error returned when parsing an [`Error`] from a string fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseError(());
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not a known error symbol or error code")
+    }
+}
+impl std::error::Error for ParseError {}
+
+/// Parses a symbol name such as `ERROR_FILE_NOT_FOUND`, a decimal code, or a
+/// `0x`-prefixed hex code.
+impl FromStr for Error {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let s = s.trim();
+        let code = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok().or_else(|| code_from_symbol(s)),
+        };
+        code.map(Error::with_code).ok_or(ParseError(()))
+    }
+}
+
+/// A `Result` whose error defaults to a Windows API [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
 /// Retrieve the last error. Equivilent to windows API call GetLastError().
 pub fn last_error() -> Error {
     let code = unsafe { GetLastError() };
     Error::with_code(code)
 }
+/// Clears the last error, runs `f`, and returns the last error if `f` left a
+/// nonzero one behind.
+///
+/// This tells "nothing failed" apart from a stale code left by an earlier
+/// call, for APIs whose only failure signal is the last error.
+pub fn probe<F: FnOnce()>(f: F) -> Option<Error> {
+    unsafe { SetLastError(0) };
+    f();
+    Some(last_error()).filter(|e| !e.is_success())
+}
+/// Returns `h` unless it is null or `INVALID_HANDLE_VALUE`, in which case the last
+/// error is returned. This is the same as `h.into_win_result()`.
+///
+/// `INVALID_HANDLE_VALUE` is also the pseudo-handle returned by
+/// `GetCurrentProcess`, so don't pass that here.
+pub fn handle_or_last(h: HANDLE) -> Result<HANDLE> {
+    h.into_win_result()
+}
+/// Returns the last error if `result` is an `Err`, or None if it is `Ok`.
+///
+/// Call this immediately after a failing operation, such as a std function,
+/// to capture the code it left behind before anything else overwrites it.
+pub fn last_error_after<T, E>(result: &std::result::Result<T, E>) -> Option<Error> {
+    result.as_ref().err().map(|_| last_error())
+}
 /// Creates an error from the HRESULT value.
 pub fn from_hresult(hr: i32) -> Error {
-    let code = HRESULT_CODE(hr) as u32;
-    Error::with_code(code)
+    Error::with_code(win32_from_hresult(hr))
+}
+/// Converts a Win32 error code to an HRESULT, like the `HRESULT_FROM_WIN32` macro.
+pub const fn hresult_from_win32(code: u32) -> i32 {
+    if code as i32 <= 0 {
+        code as i32
+    } else {
+        ((code & 0xFFFF) | (FACILITY_WIN32 << 16) | HRESULT_SEVERITY_BIT) as i32
+    }
+}
+/// Extracts the Win32 error code from an HRESULT, like the `HRESULT_CODE` macro.
+/// This takes the low 16 bits without checking the facility.
+pub const fn win32_from_hresult(hr: i32) -> u32 {
+    (hr & 0xFFFF) as u32
+}
+/// Counts the distinct errors in `errors`. The result is sorted by descending
+/// count, then by code.
+pub fn summarize(errors: &[Error]) -> Vec<(Error, usize)> {
+    let mut counts = HashMap::new();
+    for &e in errors {
+        *counts.entry(e).or_insert(0) += 1;
+    }
+    let mut summary: Vec<(Error, usize)> = counts.into_iter().collect();
+    summary.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    summary
+}
+
+#[cfg(feature = "intern")]
+static INTERNED: Mutex<BTreeSet<Arc<str>>> = Mutex::new(BTreeSet::new());
+#[cfg(feature = "intern")]
+static LEAKED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+#[cfg(feature = "cache")]
+static CACHED: Mutex<BTreeMap<u32, Option<Arc<str>>>> = Mutex::new(BTreeMap::new());
+
+/// Runs each BOOL-style call in order and stops at the first that returns
+/// false, returning the last error it left behind. Later calls are not run.
+pub fn try_all(calls: &[&dyn Fn() -> bool]) -> Result<()> {
+    for call in calls {
+        if !call() {
+            return Err(last_error());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the (major, minor, build) version of Windows, as reported by
+/// `RtlGetVersion`.
+///
+/// Message text sometimes changes between Windows versions, so this lets callers
+/// key their handling on the version that produced a message. Unlike
+/// `GetVersionEx`, the result does not depend on the application manifest.
+/// Returns `(0, 0, 0)` if the version cannot be determined.
+pub fn os_version() -> (u32, u32, u32) {
+    let mut info: OSVERSIONINFOW = unsafe { std::mem::zeroed() };
+    info.dwOSVersionInfoSize = std::mem::size_of::<OSVERSIONINFOW>() as u32;
+    if unsafe { RtlGetVersion(&mut info) } < 0 {
+        return (0, 0, 0);
+    }
+    (info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber)
+}
+
+static DEFAULT_LANGUAGE: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the language id used when formatting messages. 0 (the default)
+/// lets FormatMessage pick a language using its normal search order.
+pub fn default_language() -> u16 {
+    DEFAULT_LANGUAGE.load(Ordering::Relaxed) as u16
+}
+/// Sets the language id used when formatting messages for the whole process.
+/// Pass 0 to restore FormatMessage's normal search order.
+///
+/// With the `cache` feature, this also clears the cache behind
+/// [`Error::message_cached`].
+pub fn set_default_language(lang: u16) {
+    DEFAULT_LANGUAGE.store(lang as u32, Ordering::Relaxed);
+    #[cfg(feature = "cache")]
+    CACHED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+/// Reads the user's UI language and installs it as the default language.
+/// Intended to be called once at startup.
+pub fn use_user_default_language() {
+    set_default_language(user_default_language());
+}
+/// Returns the user's UI language, the one [`use_user_default_language`]
+/// installs. This is 0 on platforms other than Windows.
+pub fn user_default_language() -> u16 {
+    unsafe { GetUserDefaultUILanguage() }
 }
 impl Error {
     /// Retrieve the last error. Equivilent to windows API call GetLastError().
     pub fn last() -> Self {
         last_error()
     }
+    /// Retrieve the last error together with the time it was read. The time is
+    /// taken immediately after reading the error, for ordering errors in logs.
+    pub fn last_with_timestamp() -> (Self, SystemTime) {
+        let error = last_error();
+        (error, SystemTime::now())
+    }
+    /// Captures the last error for use elsewhere, such as on another thread.
+    ///
+    /// Call this immediately after the failing API, on the same thread: the last
+    /// error is per-thread and is overwritten by later calls, including ones made
+    /// by an async runtime between polls. The returned value is a self-contained,
+    /// `Send` copy of the code.
+    ///
+    /// ```no_run
+    /// # unsafe fn DeleteFileW(_: *const u16) -> i32 { 0 }
+    /// # async fn report(_: winerr::Error) {}
+    /// # let path = [0u16];
+    /// async {
+    ///     if unsafe { DeleteFileW(path.as_ptr()) } == 0 {
+    ///         // Capture before the next await, which may run other code first.
+    ///         let err = winerr::Error::snapshot();
+    ///         report(err).await;
+    ///     }
+    /// };
+    /// ```
+    pub fn snapshot() -> Self {
+        last_error()
+    }
     /// Returns the error code
     pub fn code(self) -> u32 {
         self.code
     }
+    /// Returns the error code as 0x-prefixed, 8 digit uppercase hex, e.g. `0x00000005`.
+    pub fn code_hex(self) -> String {
+        format!("0x{:08X}", self.code)
+    }
+    /// Returns an adapter that displays only the code: in decimal with `{}`, or in
+    /// hex like [`Error::code_hex`] with `{:#}`.
+    pub fn code_display(self) -> CodeDisplay {
+        CodeDisplay(self)
+    }
+    /// Returns the winerror.h name of the code, such as `"ERROR_ACCESS_DENIED"`.
+    ///
+    /// Only a curated set of common codes is known; None is returned for the rest.
+    pub fn symbol_name(self) -> Option<&'static str> {
+        SYMBOLS
+            .binary_search_by_key(&self.code, |&(code, _)| code)
+            .ok()
+            .map(|i| SYMBOLS[i].1)
+    }
+    /// Returns the symbol name, hex code, and message in one string, such as
+    /// `ERROR_ACCESS_DENIED (0x00000005): Access is denied.`
+    ///
+    /// The symbol is omitted if unknown, and the message is `Unknown error` if
+    /// the code has no message.
+    pub fn to_full_string(&self) -> String {
+        let message = self.message_or("Unknown error");
+        match self.symbol_name() {
+            Some(symbol) => format!("{} ({}): {}", symbol, self.code_hex(), message),
+            None => format!("{}: {}", self.code_hex(), message),
+        }
+    }
+    /// Formats the error with the parts chosen in `opts`. With symbol and hex
+    /// included this matches [`Error::to_full_string`]:
+    /// `ERROR_ACCESS_DENIED (0x00000005): Access is denied.`
+    ///
+    /// The numbers go in parentheses after the symbol, or first if there is no
+    /// symbol, and the message is `Unknown error` if the code has no message.
+    pub fn display_string(&self, opts: DisplayOpts) -> String {
+        let lang = opts.language.unwrap_or_else(default_language);
+        let mut message =
+            fmt_error_lang(self.code, lang).unwrap_or_else(|| "Unknown error".to_string());
+        if opts.single_line {
+            message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        let mut numbers = Vec::new();
+        if opts.include_code {
+            numbers.push(self.code.to_string());
+        }
+        if opts.include_hex {
+            numbers.push(self.code_hex());
+        }
+        let numbers = numbers.join(", ");
+        let symbol = self.symbol_name().filter(|_| opts.include_symbol);
+        match (symbol, numbers.is_empty()) {
+            (Some(symbol), true) => format!("{}: {}", symbol, message),
+            (Some(symbol), false) => format!("{} ({}): {}", symbol, numbers, message),
+            (None, false) => format!("{}: {}", numbers, message),
+            (None, true) => message,
+        }
+    }
     /// Creates an error with the specified code.
     pub fn with_code(code: u32) -> Self {
         Self { code }
     }
+    /// Creates an error with the specified Win32 code, returning it as `Err`
+    /// if the value has the high bit set.
+    ///
+    /// Win32 codes never use the high bit, but failure HRESULTs always do, so a
+    /// value like `0x80070005` most likely came from a COM API and belongs in
+    /// [`Error::from_hresult`] instead. This check is advisory: the `Err` still
+    /// carries the value unchanged, for callers that want it anyway.
+    pub fn with_win32_code(code: u32) -> std::result::Result<Self, Self> {
+        if code & HRESULT_SEVERITY_BIT == 0 {
+            Ok(Self::with_code(code))
+        } else {
+            Err(Self::with_code(code))
+        }
+    }
+    /// Returns None if `success` is true, and otherwise an error with `code`.
+    ///
+    /// This is for APIs that report the code explicitly, such as through an
+    /// out-parameter, where `last_error()` could be stale.
+    pub fn from_bool_and_code(success: bool, code: u32) -> Option<Self> {
+        if success {
+            None
+        } else {
+            Some(Self::with_code(code))
+        }
+    }
+    /// Interprets the `LSTATUS` returned by a registry function, which is a Win32
+    /// code stored in a signed `LONG`. Returns None for `ERROR_SUCCESS`.
+    pub fn from_registry(status: i32) -> Option<Self> {
+        if status == 0 {
+            None
+        } else {
+            Some(Self::with_code(status as u32))
+        }
+    }
+    /// Prepares a code captured elsewhere, such as one a child process wrote to
+    /// shared memory, for formatting in `language`.
+    ///
+    /// Formatting depends only on the code and language, never on the calling
+    /// process or its last error, so captured codes format the same as they would
+    /// in the process that produced them. The last error is only read when a
+    /// message cannot be formatted, to explain why in the fallback text.
+    pub fn from_shared_code(code: u32, language: u16) -> LazyError {
+        LazyError {
+            error: Self::with_code(code),
+            language,
+        }
+    }
+    /// Reads an error code from an out-parameter written by an API, returning
+    /// None if `ptr` is null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null, or valid for reading an initialized, properly aligned `u32`.
+    pub unsafe fn from_out_ptr(ptr: *const u32) -> Option<Self> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Self::with_code(ptr.read()))
+        }
+    }
+    /// Creates an error keeping the full COM HRESULT as its code, unlike
+    /// [`Error::from_hresult`] which keeps only the Win32 part.
+    ///
+    /// The message comes from the system table, which covers common HRESULTs
+    /// such as `E_NOINTERFACE`, then any registered message modules. An HRESULT
+    /// wrapping a Win32 code, such as `E_ACCESSDENIED`, falls back to that code's
+    /// message.
+    pub fn from_com(hr: i32) -> Self {
+        Self::with_code(hr as u32)
+    }
     /// Creates an error from the HRESULT value.
     pub fn from_hresult(hr: i32) -> Self {
         from_hresult(hr)
     }
+    /// Creates a [`CrtError`] from a C runtime errno value. These are formatted
+    /// with the CRT's own messages rather than the Win32 system table.
+    #[cfg(feature = "crt")]
+    pub fn from_crt_errno(errno: i32) -> CrtError {
+        CrtError::new(errno)
+    }
+    /// Creates a [`RichError`] from an `io::Error`, using its OS error code and
+    /// keeping the `io::Error` as the `source()`. An `io::Error` without an OS
+    /// error code gives `ERROR_INVALID_FUNCTION` (1), so that it never reads as
+    /// success, and displays the `io::Error`'s own message.
+    pub fn from_io_with_source(e: io::Error) -> RichError {
+        let code = e
+            .raw_os_error()
+            .map_or(ERROR_INVALID_FUNCTION, |code| code as u32);
+        RichError {
+            error: Self::with_code(code),
+            source: Some(e),
+            context: None,
+        }
+    }
+    /// Like [`Error::from_io_with_source`], but also prefixes the display with
+    /// `context`, as in `"opening config: Access is denied."`.
+    pub fn wrap_io(e: io::Error, context: &str) -> RichError {
+        RichError {
+            context: Some(context.to_string()),
+            ..Self::from_io_with_source(e)
+        }
+    }
+    /// Creates an error representing no error, `ERROR_SUCCESS` (0).
+    pub fn success() -> Self {
+        Self::with_code(0)
+    }
+    /// Returns true if the code is `ERROR_SUCCESS` (0).
+    pub fn is_success(self) -> bool {
+        self.code == 0
+    }
+    /// Panics with `msg` and the formatted message if the code is not
+    /// `ERROR_SUCCESS` (0).
+    #[track_caller]
+    pub fn expect_success(self, msg: &str) {
+        if !self.is_success() {
+            panic!("{}: {} (code {})", msg, self, self.code);
+        }
+    }
+    /// Returns the error as a [`NonSuccessError`], or None if the code is
+    /// `ERROR_SUCCESS` (0).
+    pub fn as_failure(self) -> Option<NonSuccessError> {
+        NonZeroU32::new(self.code).map(NonSuccessError)
+    }
+    /// Pairs the error with a help link, made of `base` followed by the hex code
+    /// (such as `0x00000005`), which is shown after the message when displayed.
+    pub fn with_help_url(self, base: &str) -> HelpfulError {
+        HelpfulError {
+            error: self,
+            url: format!("{}{}", base, self.code_hex()),
+        }
+    }
+    /// Returns the code as a `u16`, such as for an API taking a `WORD` message id,
+    /// or None if it does not fit.
+    pub fn as_u16(self) -> Option<u16> {
+        u16::try_from(self.code).ok()
+    }
+    /// Returns the code as a `NonZeroU32`, or None for `ERROR_SUCCESS`.
+    ///
+    /// `Option<NonZeroU32>` is still 4 bytes, so this is a compact way to store
+    /// many optional failures without a separate discriminant.
+    pub fn code_nonzero(self) -> Option<NonZeroU32> {
+        NonZeroU32::new(self.code)
+    }
+    /// Captures the current default language, deferring formatting until the
+    /// returned value is displayed.
+    pub fn lazy(self) -> LazyError {
+        LazyError {
+            error: self,
+            language: default_language(),
+        }
+    }
+    /// Returns the code as an `i32`, like [`io::Error::raw_os_error`].
+    ///
+    /// This is always `Some(self.code() as i32)`, including for `ERROR_SUCCESS`,
+    /// so code that is generic over both error types can use either.
+    pub fn raw_os_error(self) -> Option<i32> {
+        Some(self.code as i32)
+    }
+    /// Compares two errors after folding together codes that mean the same thing
+    /// for most callers:
+    ///
+    /// - `ERROR_PATH_NOT_FOUND` (3) is treated as `ERROR_FILE_NOT_FOUND` (2).
+    /// - `ERROR_HANDLE_DISK_FULL` (39) is treated as `ERROR_DISK_FULL` (112).
+    ///
+    /// All other codes must be equal.
+    pub fn eq_canonical(self, other: Error) -> bool {
+        canonical_code(self.code) == canonical_code(other.code)
+    }
+    /// Returns true if the code equals `code`, such as a winapi `DWORD` constant.
+    pub fn matches_dword(self, code: u32) -> bool {
+        self.code == code
+    }
+    /// Returns true if the code matches `hr`, such as a winapi `HRESULT` constant.
+    ///
+    /// This matches either a code holding the full HRESULT, or a Win32 code whose
+    /// `HRESULT_FROM_WIN32` equals `hr`.
+    pub fn matches_hresult(self, hr: i32) -> bool {
+        self.code == hr as u32 || hresult_from_win32(self.code) == hr
+    }
+    /// Returns true if the code typically means a feature is not available on this
+    /// platform: `ERROR_INVALID_FUNCTION` (1), `ERROR_NOT_SUPPORTED` (50), or
+    /// `ERROR_CALL_NOT_IMPLEMENTED` (120).
+    pub fn is_not_supported(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_INVALID_FUNCTION | ERROR_NOT_SUPPORTED | ERROR_CALL_NOT_IMPLEMENTED
+        )
+    }
+    /// Returns true if the code typically means the process lacks a privilege or
+    /// needs to be elevated: `ERROR_ACCESS_DENIED` (5), `ERROR_ELEVATION_REQUIRED`
+    /// (740), or `ERROR_PRIVILEGE_NOT_HELD` (1314).
+    ///
+    /// Access denied has many causes besides elevation, so this is only a hint.
+    pub fn needs_elevation(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_ACCESS_DENIED | ERROR_ELEVATION_REQUIRED | ERROR_PRIVILEGE_NOT_HELD
+        )
+    }
+    /// Returns a short, actionable hint for a few common codes, suitable for
+    /// showing to the user alongside the message.
+    pub fn guidance(self) -> Option<&'static str> {
+        Some(match self.code {
+            ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND => "Check that the path exists.",
+            ERROR_ACCESS_DENIED | ERROR_ELEVATION_REQUIRED => "Try running as administrator.",
+            ERROR_SHARING_VIOLATION => "Close any other program that is using the file.",
+            ERROR_PRIVILEGE_NOT_HELD => {
+                "Try running as administrator, or ask an administrator to grant the required privilege."
+            }
+            _ => return None,
+        })
+    }
+    /// Returns true if the code means the disk is out of space: `ERROR_HANDLE_DISK_FULL`
+    /// (39) or `ERROR_DISK_FULL` (112).
+    pub fn is_disk_full(self) -> bool {
+        matches!(self.code, ERROR_HANDLE_DISK_FULL | ERROR_DISK_FULL)
+    }
+    /// Returns true if the code is a storage condition: a full disk, an exceeded
+    /// quota (1295, 1816), or a media problem such as a write-protected or
+    /// unreadable disk (19, 21, 23, 27, 29, 30).
+    pub fn is_storage_error(self) -> bool {
+        self.is_disk_full()
+            || matches!(
+                self.code,
+                ERROR_WRITE_PROTECT
+                    | ERROR_NOT_READY
+                    | ERROR_CRC
+                    | ERROR_SECTOR_NOT_FOUND
+                    | ERROR_WRITE_FAULT
+                    | ERROR_READ_FAULT
+                    | ERROR_DISK_QUOTA_EXCEEDED
+                    | ERROR_NOT_ENOUGH_QUOTA
+            )
+    }
+    /// Returns true if the operation was cancelled rather than failing:
+    /// `ERROR_OPERATION_ABORTED` (995), for I/O cancelled by `CancelIo` or a
+    /// thread exit, or `ERROR_CANCELLED` (1223), for an operation the user cancelled.
+    pub fn is_cancelled(self) -> bool {
+        matches!(self.code, ERROR_OPERATION_ABORTED | ERROR_CANCELLED)
+    }
+    /// Returns true if the code points at a handle-management problem:
+    /// `ERROR_TOO_MANY_OPEN_FILES` (4), `ERROR_INVALID_HANDLE` (6),
+    /// `ERROR_ARENA_TRASHED` (7), or `ERROR_INVALID_TARGET_HANDLE` (114).
+    ///
+    /// Using a handle after it was closed usually shows up as `ERROR_INVALID_HANDLE`.
+    pub fn is_handle_error(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_TOO_MANY_OPEN_FILES
+                | ERROR_INVALID_HANDLE
+                | ERROR_ARENA_TRASHED
+                | ERROR_INVALID_TARGET_HANDLE
+        )
+    }
+    /// Returns true if the code is an authentication failure:
+    /// `ERROR_NO_SUCH_USER` (1317), `ERROR_LOGON_FAILURE` (1326),
+    /// `ERROR_PASSWORD_EXPIRED` (1330), `ERROR_ACCOUNT_DISABLED` (1331), or
+    /// `ERROR_ACCOUNT_LOCKED_OUT` (1909).
+    pub fn is_auth_error(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_NO_SUCH_USER
+                | ERROR_LOGON_FAILURE
+                | ERROR_PASSWORD_EXPIRED
+                | ERROR_ACCOUNT_DISABLED
+                | ERROR_ACCOUNT_LOCKED_OUT
+        )
+    }
+    /// Returns a default delay before retrying, for codes that usually clear up
+    /// on their own, or None if retrying is unlikely to help.
+    ///
+    /// `ERROR_SHARING_VIOLATION` (32) and `ERROR_LOCK_VIOLATION` (33) suggest
+    /// 100ms, as another process briefly has the file open or locked.
+    /// `ERROR_BUSY` (170) suggests 500ms.
+    pub fn suggested_backoff(self) -> Option<Duration> {
+        match self.code {
+            ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION => Some(Duration::from_millis(100)),
+            ERROR_BUSY => Some(Duration::from_millis(500)),
+            _ => None,
+        }
+    }
+    /// Returns true if the code means an operation timed out: `ERROR_SEM_TIMEOUT`
+    /// (121), `WAIT_TIMEOUT` (258), `ERROR_TIMEOUT` (1460), or `WSAETIMEDOUT` (10060).
+    pub fn is_timeout(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_SEM_TIMEOUT | WAIT_TIMEOUT | ERROR_TIMEOUT | WSAETIMEDOUT
+        )
+    }
+    /// Returns true if the code is a named pipe condition: `ERROR_BROKEN_PIPE`
+    /// (109), `ERROR_PIPE_BUSY` (231), `ERROR_NO_DATA` (232),
+    /// `ERROR_PIPE_NOT_CONNECTED` (233), or `ERROR_PIPE_CONNECTED` (535).
+    ///
+    /// `ERROR_PIPE_CONNECTED` is reported by `ConnectNamedPipe` when a client
+    /// connected first, which servers usually treat as success.
+    pub fn is_pipe_error(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_BROKEN_PIPE
+                | ERROR_PIPE_BUSY
+                | ERROR_NO_DATA
+                | ERROR_PIPE_NOT_CONNECTED
+                | ERROR_PIPE_CONNECTED
+        )
+    }
+    /// Returns true if the code is `ERROR_PIPE_BUSY` (231), meaning every instance
+    /// of the pipe is in use. Clients should wait with `WaitNamedPipe` and connect
+    /// again.
+    pub fn is_pipe_retryable(self) -> bool {
+        self.code == ERROR_PIPE_BUSY
+    }
+    /// Returns true if the code is `ERROR_ALREADY_EXISTS` (183).
+    ///
+    /// Besides failed creation, `CreateMutexW` and similar functions succeed but
+    /// set this code when the named object already existed, which is the usual
+    /// way for an app to tell another instance is running.
+    pub fn is_already_exists(self) -> bool {
+        self.code == ERROR_ALREADY_EXISTS
+    }
+    /// Returns true if the code is a device or media failure: `ERROR_BAD_UNIT`
+    /// (20), `ERROR_NOT_READY` (21), `ERROR_GEN_FAILURE` (31), `ERROR_DEV_NOT_EXIST`
+    /// (55), `ERROR_MEDIA_CHANGED` (1110), `ERROR_NO_MEDIA_IN_DRIVE` (1112), or
+    /// `ERROR_IO_DEVICE` (1117).
+    pub fn is_device_error(self) -> bool {
+        matches!(
+            self.code,
+            ERROR_BAD_UNIT
+                | ERROR_NOT_READY
+                | ERROR_GEN_FAILURE
+                | ERROR_DEV_NOT_EXIST
+                | ERROR_MEDIA_CHANGED
+                | ERROR_NO_MEDIA_IN_DRIVE
+                | ERROR_IO_DEVICE
+        )
+    }
+    /// Returns true if the code is `ERROR_IO_PENDING` (997), meaning an overlapped
+    /// operation was started and has not completed yet.
+    ///
+    /// ```no_run
+    /// # fn start_read() -> winerr::Result<()> { Ok(()) }
+    /// # fn wait_for_completion() -> winerr::Result<()> { Ok(()) }
+    /// match start_read() {
+    ///     Ok(()) => {}
+    ///     Err(e) if e.is_io_pending() => wait_for_completion()?,
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # Ok::<(), winerr::Error>(())
+    /// ```
+    pub fn is_io_pending(self) -> bool {
+        self.code == ERROR_IO_PENDING
+    }
+    /// Returns true if the code is `ERROR_MORE_DATA` (234), meaning the call should
+    /// be made again with a bigger buffer.
+    pub fn is_more_data(self) -> bool {
+        self.code == ERROR_MORE_DATA
+    }
+    /// Returns true if the call should be made again with a bigger buffer:
+    /// `ERROR_INSUFFICIENT_BUFFER` (122) or `ERROR_MORE_DATA` (234).
+    ///
+    /// ```no_run
+    /// # fn query(_: &mut Vec<u8>) -> winerr::Result<()> { Ok(()) }
+    /// let mut buf = Vec::with_capacity(64);
+    /// while let Err(e) = query(&mut buf) {
+    ///     if !e.is_buffer_too_small() {
+    ///         return Err(e);
+    ///     }
+    ///     buf.reserve(buf.capacity() * 2);
+    /// }
+    /// # Ok::<(), winerr::Error>(())
+    /// ```
+    pub fn is_buffer_too_small(self) -> bool {
+        matches!(self.code, ERROR_INSUFFICIENT_BUFFER | ERROR_MORE_DATA)
+    }
+    /// Interprets the return value of a wait function such as `WaitForSingleObject`.
+    ///
+    /// Returns None whenever the wait ended without failing: for `WAIT_OBJECT_0 + n`
+    /// and `WAIT_ABANDONED_0 + n`, whichever handle `n` was, and for
+    /// `WAIT_IO_COMPLETION`. Callers that care which handle it was, or whether a
+    /// mutex was abandoned, should inspect `result` themselves.
+    ///
+    /// Returns the last error for `WAIT_FAILED`. Any other value, such as
+    /// `WAIT_TIMEOUT` (258), is used as the error code directly.
+    pub fn from_wait_result(result: u32) -> Option<Self> {
+        let in_range = |base: u32| (base..base + MAXIMUM_WAIT_OBJECTS).contains(&result);
+        match result {
+            _ if in_range(WAIT_OBJECT_0) || in_range(WAIT_ABANDONED) => None,
+            WAIT_IO_COMPLETION => None,
+            WAIT_FAILED => Some(last_error()),
+            code => Some(Self::with_code(code)),
+        }
+    }
+    /// Returns true if the code falls within the given band.
+    pub fn in_range(self, band: ErrorBand) -> bool {
+        match band {
+            ErrorBand::System => self.code <= 15999,
+            ErrorBand::Rpc => (1700..=1999).contains(&self.code),
+            ErrorBand::Winsock => (10000..=11999).contains(&self.code),
+            ErrorBand::Setup => self.code & 0xFFFF_F000 == 0xE000_0000,
+            ErrorBand::Custom => self.code & 0x2000_0000 != 0,
+        }
+    }
+    /// Returns true if the code is a success-with-information HRESULT, such as
+    /// `DRAGDROP_S_DROP`: the severity bit is clear and the value is above the
+    /// Win32 range.
+    ///
+    /// Nonzero codes up to `0xFFFF` are taken to be Win32 failures, so small
+    /// success HRESULTs like `S_FALSE` (1) cannot be told apart from them and are
+    /// not reported as warnings.
+    pub fn is_warning(self) -> bool {
+        severity(self.code) == Severity::Warning
+    }
+    /// Returns a label for the severity: `"Success"` for 0, `"Warning"` for
+    /// success HRESULTs (see [`Error::is_warning`]), or `"Error"` for Win32 codes
+    /// and HRESULTs with the severity bit set.
+    ///
+    /// Returns None if the reserved bit is set, as in NTSTATUS values, since the
+    /// code is then not an HRESULT at all.
+    pub fn severity_label(self) -> Option<&'static str> {
+        if self.code & HRESULT_RESERVED_BIT != 0 {
+            return None;
+        }
+        Some(match severity(self.code) {
+            Severity::Success => "Success",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        })
+    }
+    /// Returns the broad kind of failure the code describes.
+    pub fn category(self) -> Category {
+        match self.code {
+            0 => Category::Success,
+            ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND => Category::NotFound,
+            ERROR_FILE_EXISTS | ERROR_ALREADY_EXISTS => Category::AlreadyExists,
+            ERROR_INVALID_PARAMETER => Category::InvalidInput,
+            ERROR_NOT_ENOUGH_MEMORY | ERROR_OUTOFMEMORY => Category::OutOfMemory,
+            WSAEACCES => Category::PermissionDenied,
+            _ if self.needs_elevation() => Category::PermissionDenied,
+            _ if self.is_timeout() => Category::Timeout,
+            _ if self.is_storage_error() => Category::Storage,
+            _ if self.is_cancelled() => Category::Cancelled,
+            _ if self.is_not_supported() => Category::NotSupported,
+            _ if self.in_range(ErrorBand::Winsock) => Category::Network,
+            _ => Category::Other,
+        }
+    }
+    /// Returns an `io::ErrorKind` for common codes, and `io::ErrorKind::Other`
+    /// for everything else.
+    ///
+    /// The table is the same on every platform, unlike
+    /// `io::Error::from_raw_os_error(code).kind()`, which is only meaningful on
+    /// Windows. It is not std's table: std maps more codes, some to newer kinds
+    /// such as `StorageFull`, so the two can disagree.
+    pub fn io_error_kind(self) -> io::ErrorKind {
+        use io::ErrorKind::*;
+        match self.code {
+            ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND => NotFound,
+            ERROR_ACCESS_DENIED | WSAEACCES => PermissionDenied,
+            ERROR_FILE_EXISTS | ERROR_ALREADY_EXISTS => AlreadyExists,
+            ERROR_BROKEN_PIPE | ERROR_NO_DATA => BrokenPipe,
+            ERROR_INVALID_PARAMETER => InvalidInput,
+            ERROR_NOT_ENOUGH_MEMORY | ERROR_OUTOFMEMORY => OutOfMemory,
+            ERROR_SEM_TIMEOUT
+            | WAIT_TIMEOUT
+            | ERROR_TIMEOUT
+            | ERROR_OPERATION_ABORTED
+            | WSAETIMEDOUT => TimedOut,
+            WSAEWOULDBLOCK => WouldBlock,
+            WSAEADDRINUSE => AddrInUse,
+            WSAEADDRNOTAVAIL => AddrNotAvailable,
+            WSAECONNABORTED => ConnectionAborted,
+            WSAECONNRESET => ConnectionReset,
+            WSAECONNREFUSED => ConnectionRefused,
+            WSAENOTCONN => NotConnected,
+            _ => Other,
+        }
+    }
+    /// Converts into an `io::Error` whose kind comes from [`Error::io_error_kind`]
+    /// and whose display is this error's.
+    ///
+    /// Unlike `io::Error::from(err)`, the kind is the same on every platform, and
+    /// the `Error` can be recovered with `get_ref` and [`downcast_win_code`].
+    pub fn into_io_with_message(self) -> io::Error {
+        io::Error::new(self.io_error_kind(), self)
+    }
+    /// Collects everything known about the code into one [`Description`].
+    pub fn describe(self) -> Description {
+        Description {
+            code: self.code,
+            hex: self.code_hex(),
+            symbol: self.symbol_name(),
+            message: self.message(),
+            category: self.category(),
+            io_kind: self.io_error_kind(),
+        }
+    }
+    /// Orders errors for a report: failures first, then warnings, then success,
+    /// and by code within each group. Use it with `sort_by`.
+    ///
+    /// Severity is judged as in [`Error::severity_label`]: plain Win32 codes
+    /// (nonzero and below `0x10000`) are failures, and larger HRESULT-shaped
+    /// values are failures if the severity bit is set and warnings otherwise.
+    pub fn cmp_by_severity(self, other: Error) -> std::cmp::Ordering {
+        severity(other.code)
+            .cmp(&severity(self.code))
+            .then(self.code.cmp(&other.code))
+    }
+    /// Returns the formatted message, or None if the code has no message.
+    pub fn message(&self) -> Option<String> {
+        fmt_error(self.code)
+    }
+    /// Returns the message for this code from the message table of `module`,
+    /// or None if the module has no message for it.
+    pub fn message_from_module(&self, module: HMODULE) -> Option<String> {
+        fmt_module_error(module, self.code, default_language())
+    }
+    /// Returns the message for this code from a message table embedded in the
+    /// current executable, or None if it has none or no message for this code.
+    pub fn message_from_current_module(&self) -> Option<String> {
+        let module = unsafe { GetModuleHandleW(std::ptr::null()) };
+        self.message_from_module(module)
+    }
+    /// Loads the named DLL as a data file, formats this code from its message
+    /// table, and frees it again. Returns None if the DLL cannot be loaded or
+    /// has no message for this code.
+    pub fn message_from_module_named(&self, dll: &str) -> Option<String> {
+        let module = load_data_file(dll);
+        if module.is_null() {
+            return None;
+        }
+        let message = self.message_from_module(module);
+        unsafe { FreeLibrary(module) };
+        message
+    }
+    /// Returns the code and message as a tuple, for recording as separate
+    /// structured logging fields.
+    pub fn as_fields(&self) -> (u32, Option<String>) {
+        (self.code, self.message())
+    }
+    /// Returns the code and message as a JSON object, such as
+    /// `{"code":5,"message":"Access is denied."}`. The message is `null` if the
+    /// code has no message.
+    pub fn to_json(&self) -> String {
+        let message = match self.message() {
+            Some(s) => json_string(&s),
+            None => "null".to_string(),
+        };
+        format!("{{\"code\":{},\"message\":{}}}", self.code, message)
+    }
+    /// Returns the formatted message with every line after the first prefixed by
+    /// `indent` spaces, so it lines up under a label in a nested error report.
+    pub fn message_indented(&self, indent: usize) -> Option<String> {
+        self.message().map(|m| indent_lines(&m, indent))
+    }
+    /// Returns the formatted message, or the decimal code if the code has no message.
+    pub fn message_or_code(&self) -> String {
+        self.message().unwrap_or_else(|| self.code.to_string())
+    }
+    /// Returns the formatted message with `\r\n` line breaks turned into `\n`.
+    /// Unlike [`Error::message_summary`], every line is kept.
+    pub fn message_lf(&self) -> Option<String> {
+        self.message().map(|s| s.replace("\r\n", "\n"))
+    }
+    /// Returns the formatted message escaped for HTML, with `&`, `<`, `>`, `"` and
+    /// `'` replaced by entities and `\r\n` line breaks turned into `<br>`.
+    pub fn message_html(&self) -> Option<String> {
+        self.message().map(|s| html_escape(&s))
+    }
+    /// Returns the formatted message, or `"Unknown error (code N)"` if the code has
+    /// no message. The result is never empty.
+    pub fn message_display(&self) -> String {
+        self.message()
+            .unwrap_or_else(|| format!("Unknown error (code {})", self.code))
+    }
+    /// Returns true if the formatted message equals `text` after normalizing both:
+    /// runs of whitespace, including line breaks, become one space, and leading
+    /// and trailing whitespace and trailing punctuation (`.`, `!`, `?`, `:`, `;`,
+    /// `,`) are removed. Returns false if the code has no message.
+    pub fn message_matches(&self, text: &str) -> bool {
+        self.message()
+            .is_some_and(|m| normalize_message(&m) == normalize_message(text))
+    }
+    /// Returns the first line of the formatted message, or None if the code has
+    /// no message. Single line messages are returned whole.
+    pub fn message_summary(&self) -> Option<String> {
+        self.message()
+            .map(|s| s.lines().next().unwrap_or_default().trim().to_string())
+    }
+    /// Returns the number of terminal columns taken by [`Error::message_summary`],
+    /// or None if the code has no message.
+    #[cfg(feature = "unicode-width")]
+    pub fn display_width(&self) -> Option<usize> {
+        self.message_summary()
+            .map(|s| unicode_width::UnicodeWidthStr::width(s.as_str()))
+    }
+    /// Returns the formatted message with a leading `{Header}` line removed, as
+    /// found in NTSTATUS-derived messages like code 609. Messages without such a
+    /// header are returned unchanged.
+    pub fn message_without_brace_header(&self) -> Option<String> {
+        self.message().map(|s| match split_brace_header(&s) {
+            Some((_, body)) => body.to_string(),
+            None => s,
+        })
+    }
+    /// Splits the formatted message into a title and a body, for UIs that show
+    /// them separately.
+    ///
+    /// The title is a leading `{Header}` (braces included) if there is one, and
+    /// otherwise the first line. Single line messages are returned whole as the
+    /// title, with an empty body.
+    pub fn title_and_body(&self) -> Option<(String, String)> {
+        let message = self.message()?;
+        let (title, body) = split_title(&message);
+        Some((title.to_string(), body.to_string()))
+    }
+    /// Returns the formatted message, choosing whether inserts such as `%1` are
+    /// ignored. `message()` always ignores them, leaving the raw template.
+    ///
+    /// No insert arguments are supplied, so with `ignore` false any message that
+    /// contains inserts fails to format and None is returned.
+    pub fn message_with_inserts_ignored(&self, ignore: bool) -> Option<String> {
+        let lang = default_language();
+        lookup_message(self.code, |mut flags, source, code| {
+            if !ignore {
+                flags &= !FORMAT_MESSAGE_IGNORE_INSERTS;
+            }
+            fmt_message(flags, source, code, lang)
+        })
+    }
+    /// Returns the message with its inserts replaced by `args` (`%1` is
+    /// `args[0]`) and lines wrapped at `max_width` characters.
+    ///
+    /// A `max_width` of 0 keeps the message's own line breaks, and 255 removes
+    /// them, as with `FORMAT_MESSAGE_MAX_WIDTH_MASK`. Returns None if the code
+    /// has no message, or if the message needs more arguments than were given or
+    /// uses an insert format other than a string.
+    pub fn format_full(&self, args: &[&str], max_width: u8) -> Option<String> {
+        let args: Vec<Insert> = args.iter().map(|&arg| Insert::Str(arg)).collect();
+        self.format_inserts(&args, max_width)
+    }
+    /// Returns the message with its inserts replaced by typed `args` (`%1` is `args[0]`), so that numeric inserts such as `%1!d!`
+    /// can be filled.
+    ///
+    /// Returns None if the code has no message, or if the message needs more
+    /// arguments than were given or an insert's format does not match its
+    /// argument: strings need a plain insert or `!s!`, and numbers one of `!d!`,
+    /// `!i!`, `!u!`, `!x!`, `!X!`, or `!o!`.
+    pub fn format_with_inserts(&self, args: &[Insert]) -> Option<String> {
+        self.format_inserts(args, 0)
+    }
+    fn format_inserts(&self, args: &[Insert], max_width: u8) -> Option<String> {
+        let lang = default_language();
+        // The template is found like any other message, then formatted again from
+        // the same place with the arguments substituted.
+        lookup_message(self.code, |flags, source, code| {
+            let template = fmt_message(flags, source, code, lang)?;
+            if !inserts_supplied(&template, args) {
+                return Some(None);
+            }
+            let flags = (flags & !FORMAT_MESSAGE_IGNORE_INSERTS)
+                | (max_width as u32 & FORMAT_MESSAGE_MAX_WIDTH_MASK);
+            Some(fmt_message_args(flags, source, code, lang, args).map(|s| s.trim().to_string()))
+        })
+        .flatten()
+    }
+    /// Returns the message with numbered inserts replaced from `named`, so `%1`
+    /// (or `%1!s!`) becomes `named[&1]`.
+    ///
+    /// The substitution is done in Rust rather than by FormatMessage. Inserts
+    /// whose index is not in `named`, and printf-style sequences like `%hs`, are
+    /// left as they are. An escaped `%%` becomes a single `%`.
+    pub fn format_named(&self, named: &HashMap<u32, String>) -> Option<String> {
+        self.message().map(|s| substitute_inserts(&s, named))
+    }
+    /// Writes the message into `buf` as UTF-16, exactly as FormatMessage produces
+    /// it (including any trailing line break) and followed by a NUL.
+    ///
+    /// Returns `Ok(len)` with the number of code units written, not counting the
+    /// NUL, or `Ok(0)` if the code has no message. If `buf` is too small, nothing
+    /// useful is written and `Err(required)` gives the buffer length needed.
+    pub fn format_into_buf(&self, buf: &mut [u16]) -> Result<usize, usize> {
+        fmt_error_into(self.code, buf)
+    }
+    /// Converts into an `anyhow::Error`, with [`Error::to_full_string`] attached
+    /// as context. Plain `{}` formatting shows the symbol name, code, and message;
+    /// the alternate `{:#}` form repeats the message from the underlying error.
+    #[cfg(feature = "anyhow")]
+    pub fn into_anyhow(self) -> anyhow::Error {
+        let context = self.to_full_string();
+        anyhow::Error::new(self).context(context)
+    }
+    /// Returns the inserts the message template expects, in the order they appear.
+    ///
+    /// Only FormatMessage's numbered inserts are reported. printf-style sequences
+    /// like the `%hs` in some NTSTATUS-derived messages are not inserts, and are
+    /// left untouched by FormatMessage.
+    pub fn insert_spec(&self) -> Vec<InsertKind> {
+        self.message()
+            .map(|s| parse_inserts(&s))
+            .unwrap_or_default()
+    }
+    /// Returns the message in the first language of `langs` that has one,
+    /// falling back to the neutral language (0) if none of them do.
+    pub fn message_first_available(&self, langs: &[u16]) -> Option<String> {
+        langs
+            .iter()
+            .chain(std::iter::once(&0))
+            .find_map(|&lang| fmt_error_lang(self.code, lang))
+    }
+    /// Returns the formatted message as a shared `Arc<str>`. Identical messages
+    /// share one allocation from a global interner, which suits long-running
+    /// processes that report the same few errors over and over.
+    #[cfg(feature = "intern")]
+    pub fn message_arc(&self) -> Option<Arc<str>> {
+        let message = self.message()?;
+        let mut interned = INTERNED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = interned.get(message.as_str()) {
+            return Some(existing.clone());
+        }
+        let message: Arc<str> = message.into();
+        interned.insert(message.clone());
+        Some(message)
+    }
+    /// Returns the formatted message from a process-wide cache keyed only by code.
+    ///
+    /// This suits apps that format in a single language: the cache assumes the
+    /// default language is fixed, and [`set_default_language`] clears it. Codes
+    /// with no message are cached too. For several languages at once, use a
+    /// [`MessageFormatter`].
+    #[cfg(feature = "cache")]
+    pub fn message_cached(&self) -> Option<Arc<str>> {
+        let lock = || CACHED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = lock().get(&self.code) {
+            return cached.clone();
+        }
+        // Format without holding the cache lock, so that it is never held
+        // together with the message module lock that formatting takes.
+        let message: Option<Arc<str>> = self.message().map(Into::into);
+        lock().entry(self.code).or_insert(message).clone()
+    }
+    /// Returns the formatted message as a `&'static str`, for storing in places
+    /// that need `'static` data.
+    ///
+    /// Each distinct message is leaked once into a global pool and then reused,
+    /// so memory use is bounded by the number of distinct messages formatted.
+    #[cfg(feature = "intern")]
+    pub fn message_leaked(&self) -> Option<&'static str> {
+        let message = self.message()?;
+        let mut leaked = LEAKED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&existing) = leaked.get(message.as_str()) {
+            return Some(existing);
+        }
+        let message: &'static str = Box::leak(message.into_boxed_str());
+        leaked.insert(message);
+        Some(message)
+    }
+    /// Appends the formatted message to `out` without allocating, converting from
+    /// UTF-16 directly into the fixed-capacity string.
+    ///
+    /// The message is trimmed as by [`Error::message`], and nothing is written if
+    /// the code has no message. If it does not fit, `out` is left unchanged and
+    /// an error is returned.
+    #[cfg(feature = "arrayvec")]
+    pub fn format_into_array<const N: usize>(
+        &self,
+        out: &mut arrayvec::ArrayString<N>,
+    ) -> Result<(), arrayvec::CapacityError> {
+        let mut buf = [0u16; BUF_SIZE];
+        let len = match fmt_error_into(self.code, &mut buf) {
+            Ok(len) => len,
+            // Longer than any system message, so it could not fit anyway.
+            Err(_) => return Err(arrayvec::CapacityError::new(())),
+        };
+        let units = trim_utf16(&buf[..len]);
+        let start = out.len();
+        for c in std::char::decode_utf16(units.iter().copied()) {
+            let c = c.unwrap_or(std::char::REPLACEMENT_CHARACTER);
+            if let Err(e) = out.try_push(c) {
+                out.truncate(start);
+                return Err(e.simplify());
+            }
+        }
+        Ok(())
+    }
+    /// Returns the formatted message as UTF-16, cut to at most `max_units` code
+    /// units. The cut never falls between the two halves of a surrogate pair, so
+    /// the result can be one unit shorter than `max_units`.
+    pub fn message_utf16_truncated(&self, max_units: usize) -> Option<Vec<u16>> {
+        let units: Vec<u16> = self.message()?.encode_utf16().collect();
+        Some(truncate_utf16(&units, max_units).to_vec())
+    }
+    /// Returns the formatted message as UTF-8 bytes, without a trailing NUL.
+    pub fn message_utf8(&self) -> Option<Vec<u8>> {
+        self.message().map(String::into_bytes)
+    }
+    /// Returns the message in US English, falling back to the neutral language
+    /// (0) if the English resources are not installed.
+    ///
+    /// This is the most stable text available for assertions in tests, but on
+    /// systems without English installed the fallback can still be localized.
+    pub fn message_en_us(&self) -> Option<String> {
+        // MAKELANGID(LANG_ENGLISH, SUBLANG_ENGLISH_US)
+        const EN_US: u16 = 0x0409;
+        self.message_first_available(&[EN_US])
+    }
+    /// Returns the message in the invariant language (`LANG_INVARIANT`, 0x007F),
+    /// falling back to the neutral language (0) if it has no resources.
+    ///
+    /// Unlike the neutral language, which follows the thread and user UI
+    /// languages, the invariant language does not depend on any locale setting,
+    /// and unlike [`Error::message_en_us`] it is not tied to one culture. Which
+    /// text it gives still depends on the installed language resources.
+    pub fn message_invariant(&self) -> Option<String> {
+        // MAKELANGID(LANG_INVARIANT, SUBLANG_NEUTRAL)
+        const INVARIANT: u16 = 0x007F;
+        self.message_first_available(&[INVARIANT])
+    }
+    /// Returns the formatted message, or `default` if the code has no message.
+    pub fn message_or(&self, default: impl Into<String>) -> String {
+        self.message().unwrap_or_else(|| default.into())
+    }
 }
 
-// TODO: fmt with user-provided args
+/// An [`Error`] whose code is known not to be `ERROR_SUCCESS` (0).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct NonSuccessError(NonZeroU32);
+impl NonSuccessError {
+    /// Returns the error code.
+    pub fn code(self) -> u32 {
+        self.0.get()
+    }
+    /// Returns the error code as a `NonZeroU32`.
+    pub fn get(self) -> NonZeroU32 {
+        self.0
+    }
+    /// Returns the error as a plain [`Error`].
+    pub fn error(self) -> Error {
+        Error::with_code(self.code())
+    }
+}
+impl From<NonSuccessError> for Error {
+    fn from(e: NonSuccessError) -> Self {
+        e.error()
+    }
+}
+impl Display for NonSuccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.error(), f)
+    }
+}
+impl std::error::Error for NonSuccessError {}
 
-fn fmt_error(code: u32) -> Option<String> {
-    const FLAGS: u32 = FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS;
-    // Longest error message I can find requires length of 419
-    const BUF_SIZE: usize = 420;
-    let mut buf = MaybeUninit::<[u16; BUF_SIZE]>::uninit();
-    let buf_ptr: *mut u16 = buf.as_mut_ptr().cast();
-    unsafe {
-        let len = FormatMessageW(
-            FLAGS,
-            NULL, // source (fmt string)
-            code, // msg id
-            0,    // lang id
-            buf_ptr,
-            BUF_SIZE as u32,
-            NULL as _, // fmt arguments
-        );
-        if len == 0 {
-            None
-        } else {
-            let slice = std::slice::from_raw_parts(buf_ptr, len as usize);
-            Some(String::from_utf16_lossy(slice))
+/// A C runtime `errno` value, as returned by some C libraries on Windows.
+///
+/// CRT errno values are a separate code space from Win32 error codes, even though
+/// they overlap numerically: errno 2 is `ENOENT` ("No such file or directory"),
+/// while Win32 code 2 is `ERROR_FILE_NOT_FOUND`. Keeping them in a separate type
+/// means they are never formatted against the wrong table.
+#[cfg(feature = "crt")]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CrtError {
+    errno: i32,
+}
+#[cfg(feature = "crt")]
+impl CrtError {
+    /// Creates an error from a CRT errno value.
+    pub fn new(errno: i32) -> Self {
+        Self { errno }
+    }
+    /// Returns the errno value.
+    pub fn errno(self) -> i32 {
+        self.errno
+    }
+    /// Returns the CRT's message for the errno value.
+    pub fn message(&self) -> Option<String> {
+        let mut buf = [0u16; 128];
+        let ret = unsafe { sys::_wcserror_s(buf.as_mut_ptr(), buf.len(), self.errno) };
+        if ret != 0 {
+            return None;
+        }
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        Some(String::from_utf16_lossy(&buf[..len]))
+    }
+}
+#[cfg(feature = "crt")]
+impl Debug for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CrtError")
+            .field("errno", &self.errno)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+#[cfg(feature = "crt")]
+impl Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message() {
+            Some(s) => write!(f, "{}", s),
+            None => write!(f, "CRT error {}", self.errno),
         }
     }
 }
+#[cfg(feature = "crt")]
+impl std::error::Error for CrtError {}
 
-impl Debug for Error {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        if let Some(s) = fmt_error(self.code()) {
-            write!(f, "{}", s.trim())
-        } else {
-            // This branch should never happen unless the
-            // error code is not a valid Windows message.
-            let fmt_err = last_error().code();
-            if let Some(s) = fmt_error(fmt_err) {
-                write!(
-                    f,
-                    "Error code {} (could not format due to internal error: {} - {})",
-                    self.code(),
-                    fmt_err,
-                    s.trim()
-                )
-            } else {
-                write!(
-                    f,
-                    "Error code {} (could not format due to internal error code: {})",
-                    self.code(),
-                    fmt_err
+/// A documented range of error codes, used to tell where a bare code came from.
+///
+/// Bands can overlap, e.g. every `Rpc` code is also a `System` code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ErrorBand {
+    /// System error codes, 0 through 15999.
+    System,
+    /// RPC errors, 1700 through 1999.
+    Rpc,
+    /// Windows Sockets errors, 10000 through 11999.
+    Winsock,
+    /// SetupAPI errors, 0xE0000000 through 0xE0000FFF.
+    Setup,
+    /// Application-defined codes, which have the customer bit (bit 29) set.
+    Custom,
+}
+
+/// Options for [`Error::display_string`]. The default shows only the message.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DisplayOpts {
+    /// Include the code in decimal.
+    pub include_code: bool,
+    /// Include the code in hex, as from [`Error::code_hex`].
+    pub include_hex: bool,
+    /// Include the winerror.h name, if known.
+    pub include_symbol: bool,
+    /// Join the lines of multi-line messages with spaces.
+    pub single_line: bool,
+    /// The language to format in, instead of [`default_language`].
+    pub language: Option<u16>,
+}
+
+/// The broad kind of failure a code describes, returned by [`Error::category`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Category {
+    /// `ERROR_SUCCESS`.
+    Success,
+    /// A file or path does not exist.
+    NotFound,
+    /// Access was denied, or a privilege or elevation is needed.
+    PermissionDenied,
+    /// A file or object already exists.
+    AlreadyExists,
+    /// A parameter was invalid.
+    InvalidInput,
+    /// Memory ran out.
+    OutOfMemory,
+    /// A disk is full, a quota was exceeded, or the media failed.
+    Storage,
+    /// An operation timed out.
+    Timeout,
+    /// An operation was cancelled.
+    Cancelled,
+    /// A function or feature is not supported.
+    NotSupported,
+    /// Any other Windows Sockets error.
+    Network,
+    /// Anything else.
+    Other,
+}
+
+/// Everything known about a code, returned by [`Error::describe`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Description {
+    /// The error code.
+    pub code: u32,
+    /// The code in hex, as from [`Error::code_hex`].
+    pub hex: String,
+    /// The winerror.h name, as from [`Error::symbol_name`].
+    pub symbol: Option<&'static str>,
+    /// The formatted message, as from [`Error::message`].
+    pub message: Option<String>,
+    /// The category, as from [`Error::category`].
+    pub category: Category,
+    /// The io error kind, as from [`Error::io_error_kind`].
+    pub io_kind: io::ErrorKind,
+}
+
+/// A typed argument for an insert, used by [`Error::format_with_inserts`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Insert<'a> {
+    /// A string, for a plain insert like `%1` or `%1!s!`.
+    Str(&'a str),
+    /// A UTF-16 string without a terminating NUL, for the same inserts as `Str`.
+    WStr(&'a [u16]),
+    /// A number, for a numeric insert like `%1!d!`.
+    U32(u32),
+}
+
+/// An insert sequence, such as `%1` or `%2!s!`, found in a message template.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct InsertKind {
+    /// The argument index, from 1 to 99.
+    pub index: u32,
+    /// The printf-style format between `!` characters, e.g. `s` for `%2!s!`.
+    pub format: Option<String>,
+}
+
+fn parse_inserts(template: &str) -> Vec<InsertKind> {
+    let mut inserts = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        // Like FormatMessage, read at most two digits, so `%123` is insert 12
+        // followed by a literal `3`.
+        let mut index = 0;
+        for _ in 0..2 {
+            match chars.peek().and_then(|d| d.to_digit(10)) {
+                Some(d) => index = index * 10 + d,
+                None => break,
+            }
+            chars.next();
+        }
+        if index == 0 {
+            // Escapes such as %%, %n, %0, or a printf-style spec that
+            // FormatMessage does not treat as an insert.
+            chars.next();
+            continue;
+        }
+        let mut format = None;
+        if chars.peek() == Some(&'!') {
+            chars.next();
+            format = Some(chars.by_ref().take_while(|&c| c != '!').collect());
+        }
+        inserts.push(InsertKind { index, format });
+    }
+    inserts
+}
+
+// Replaces each numbered insert in `template` that has an entry in `named`,
+// using the same rules as parse_inserts to find them.
+fn substitute_inserts(template: &str, named: &HashMap<u32, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(escaped) = after.strip_prefix('%') {
+            // An escaped percent sign, which FormatMessage outputs as one `%`.
+            out.push('%');
+            rest = escaped;
+            continue;
+        }
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let digits = digits.min(2);
+        let index = after[..digits].parse::<u32>().unwrap_or(0);
+        let mut end = start + 1 + digits;
+        if index != 0 && rest[end..].starts_with('!') {
+            if let Some(close) = rest[end + 1..].find('!') {
+                end += close + 2;
+            }
+        }
+        match named.get(&index).filter(|_| index != 0) {
+            Some(value) => out.push_str(value),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// Returns true if every insert in `template` can be filled from `args`.
+// FormatMessage reads arguments by index and type, so anything else would read
+// past or misinterpret the argument array.
+fn inserts_supplied(template: &str, args: &[Insert]) -> bool {
+    parse_inserts(template).iter().all(|insert| {
+        let format = insert.format.as_deref();
+        match args.get(insert.index as usize - 1) {
+            Some(Insert::Str(_)) | Some(Insert::WStr(_)) => matches!(format, None | Some("s")),
+            Some(Insert::U32(_)) => {
+                matches!(
+                    format,
+                    Some("d") | Some("i") | Some("u") | Some("x") | Some("X") | Some("o")
                 )
             }
+            None => false,
         }
+    })
+}
+
+// Collapses whitespace and drops trailing punctuation, for message_matches.
+fn normalize_message(s: &str) -> String {
+    let collapsed = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed
+        .trim_end_matches(|c: char| {
+            matches!(c, '.' | '!' | '?' | ':' | ';' | ',') || c.is_whitespace()
+        })
+        .to_string()
+}
+
+// Trims leading and trailing whitespace from UTF-16 text, as str::trim would.
+fn trim_utf16(units: &[u16]) -> &[u16] {
+    let is_space = |&c: &u16| char::from_u32(c as u32).is_some_and(char::is_whitespace);
+    let first = units
+        .iter()
+        .position(|c| !is_space(c))
+        .unwrap_or(units.len());
+    let last = units
+        .iter()
+        .rposition(|c| !is_space(c))
+        .map_or(first, |i| i + 1);
+    &units[first..last]
+}
+
+// Cuts `units` to at most `max` code units without splitting a surrogate pair.
+fn truncate_utf16(units: &[u16], max: usize) -> &[u16] {
+    if units.len() <= max {
+        return units;
+    }
+    let is_high_surrogate = |c: u16| (0xD800..=0xDBFF).contains(&c);
+    match max.checked_sub(1).map(|i| units[i]) {
+        Some(last) if is_high_surrogate(last) => &units[..max - 1],
+        _ => &units[..max],
     }
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        Debug::fmt(self, f)
+// How severe a code is, ordered from least to most severe. Nonzero codes in
+// the Win32 range are failures; above it, the HRESULT severity bit decides.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum Severity {
+    Success,
+    Warning,
+    Error,
+}
+
+fn severity(code: u32) -> Severity {
+    if code == 0 {
+        Severity::Success
+    } else if code <= 0xFFFF || code & HRESULT_SEVERITY_BIT != 0 {
+        Severity::Error
+    } else {
+        Severity::Warning
     }
 }
 
-impl From<Error> for std::io::Error {
-    fn from(e: Error) -> Self {
-        Self::from_raw_os_error(e.code() as i32)
+// Maps codes that eq_canonical treats as equivalent onto one of them.
+fn canonical_code(code: u32) -> u32 {
+    match code {
+        ERROR_PATH_NOT_FOUND => ERROR_FILE_NOT_FOUND,
+        ERROR_HANDLE_DISK_FULL => ERROR_DISK_FULL,
+        code => code,
     }
 }
 
-#[test]
-fn test_fmt() {
-    let err = Error::with_code(0);
-    assert_eq!(format!("{}", err), "The operation completed successfully.");
-    let err = Error::with_code(1);
-    assert_eq!(format!("{}", err), "Incorrect function.");
-    let err = Error::with_code(52);
-    assert_eq!(format!("{}", err), "You were not connected because a duplicate name exists on the network. If joining a domain, go to System in Control Panel to change the computer name and try again. If joining a workgroup, choose another workgroup name.");
-    let err = Error::with_code(192);
-    assert_eq!(format!("{}", err), "The operating system cannot run %1.");
-    let err = Error::with_code(560);
-    assert_eq!(format!("{}", err), "Indicates that an attempt was made to assign protection to a file system file or directory and one of the SIDs in the security descriptor could not be translated into a GUID that could be stored by the file system.\r\nThis causes the protection attempt to fail, which may cause a file creation attempt to fail.");
-    let err = Error::with_code(609);
-    assert_eq!(format!("{}", err), "{Invalid DLL Entrypoint}\r\nThe dynamic link library %hs is not written correctly. The stack pointer has been left in an inconsistent state.\r\nThe entrypoint should be declared as WINAPI or STDCALL. Select YES to fail the DLL load. Select NO to continue execution. Selecting NO may cause the application to operate incorrectly.");
-    let err = Error::with_code(1290);
-    assert_eq!(format!("{}", err), "The service start failed since one or more services in the same process have an incompatible service SID type setting. A service with restricted service SID type can only coexist in the same process with other services with a restricted SID type. If the service SID type for this service was just configured, the hosting process must be restarted in order to start this service.");
-    // The longest error message (that I could find)
-    let err = Error::with_code(6719);
+// Prefixes every line after the first with `indent` spaces, keeping line endings.
+fn indent_lines(s: &str, indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    let mut out = String::with_capacity(s.len());
+    for (i, line) in s.split_inclusive('\n').enumerate() {
+        if i > 0 {
+            out.push_str(&pad);
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+// Quotes and escapes a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Escapes `s` for HTML text or attribute values, turning line breaks into `<br>`.
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.replace("\r\n", "\n").chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            '\n' => out.push_str("<br>"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Splits a message into a leading "{Header}" and the remaining body.
+fn split_brace_header(message: &str) -> Option<(&str, &str)> {
+    if !message.starts_with('{') {
+        return None;
+    }
+    let end = message.find('}')? + 1;
+    let (header, rest) = message.split_at(end);
+    let body = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+        .unwrap_or(rest);
+    Some((header, body))
+}
+
+// Splits a message into a title and body for title_and_body.
+fn split_title(message: &str) -> (&str, &str) {
+    if let Some(split) = split_brace_header(message) {
+        return split;
+    }
+    match message.find('\n') {
+        Some(end) => (message[..end].trim_end(), &message[end + 1..]),
+        None => (message, ""),
+    }
+}
+
+/// Converts the return value of a Windows API call into a [`Result`].
+///
+/// ```no_run
+/// use winerr::WinResult;
+/// # unsafe fn DeleteFileW(_: *const u16) -> i32 { 1 }
+///
+/// fn delete(path: &[u16]) -> winerr::Result<()> {
+///     unsafe { DeleteFileW(path.as_ptr()) }.into_win_result()
+/// }
+/// ```
+///
+/// `BOOL` is only an alias for `i32`, so any `i32` is treated as a `BOOL`. Do
+/// not use this for APIs that return `LSTATUS`, `LONG`, or another `i32` status
+/// where 0 means success, as the result would be inverted. Use
+/// [`Error::from_registry`] for those, or cast to `u32` for the code impl.
+pub trait WinResult {
+    /// The value returned on success.
+    type Output;
+    /// Returns `Ok` if the value indicates success, otherwise the error.
+    fn into_win_result(self) -> Result<Self::Output>;
+}
+/// A `BOOL` is a failure when it is `FALSE`, in which case the last error is captured.
+impl WinResult for BOOL {
+    type Output = ();
+    fn into_win_result(self) -> Result<()> {
+        if self != 0 {
+            Ok(())
+        } else {
+            Err(last_error())
+        }
+    }
+}
+/// A `HANDLE` is a failure when it is null or `INVALID_HANDLE_VALUE`, in which case
+/// the last error is captured.
+impl WinResult for HANDLE {
+    type Output = HANDLE;
+    fn into_win_result(self) -> Result<HANDLE> {
+        if self.is_null() || self == INVALID_HANDLE_VALUE {
+            Err(last_error())
+        } else {
+            Ok(self)
+        }
+    }
+}
+/// A `u32` is treated as a returned error code, where `ERROR_SUCCESS` (0) is success.
+impl WinResult for u32 {
+    type Output = ();
+    fn into_win_result(self) -> Result<()> {
+        if self == 0 {
+            Ok(())
+        } else {
+            Err(Error::with_code(self))
+        }
+    }
+}
+
+/// Extends iterators of [`Result`]s, e.g. from many fallible operations in a row.
+pub trait ResultIterExt<T>: Iterator<Item = Result<T>> + Sized {
+    /// Skips errors whose code is in `codes`, such as `ERROR_ALREADY_EXISTS`
+    /// (183) when creating directories. Other items are passed through unchanged.
+    fn ignore_codes(self, codes: &[u32]) -> IgnoreCodes<'_, Self> {
+        IgnoreCodes { iter: self, codes }
+    }
+}
+impl<T, I: Iterator<Item = Result<T>>> ResultIterExt<T> for I {}
+
+/// The iterator returned by [`ResultIterExt::ignore_codes`].
+#[derive(Clone, Debug)]
+pub struct IgnoreCodes<'a, I> {
+    iter: I,
+    codes: &'a [u32],
+}
+impl<T, I: Iterator<Item = Result<T>>> Iterator for IgnoreCodes<'_, I> {
+    type Item = Result<T>;
+    fn next(&mut self) -> Option<Result<T>> {
+        let codes = self.codes;
+        self.iter
+            .find(|item| !matches!(item, Err(e) if codes.contains(&e.code)))
+    }
+}
+
+/// Asserts that a Windows API call succeeded, panicking with the decoded last
+/// error if it did not.
+///
+/// The expression can be anything implementing [`WinResult`], such as a `BOOL`.
+/// An optional message in `format!` syntax is included in the panic.
+///
+/// As with [`WinResult`], an `i32` is always read as a `BOOL`, so an `LSTATUS`
+/// of 0 would panic. Cast such status codes to `u32` first.
+///
+/// ```no_run
+/// # use winerr::assert_win;
+/// # unsafe fn CloseHandle(_: *mut std::ffi::c_void) -> i32 { 1 }
+/// # let handle = std::ptr::null_mut();
+/// assert_win!(unsafe { CloseHandle(handle) }, "closing {:?}", handle);
+/// ```
+#[macro_export]
+macro_rules! assert_win {
+    ($e:expr $(,)?) => {
+        if let Err(err) = $crate::WinResult::into_win_result($e) {
+            panic!(
+                "assertion failed: {}: {} (code {})",
+                stringify!($e),
+                err,
+                err.code()
+            );
+        }
+    };
+    ($e:expr, $($arg:tt)+) => {
+        if let Err(err) = $crate::WinResult::into_win_result($e) {
+            panic!(
+                "assertion failed: {}: {}: {} (code {})",
+                stringify!($e),
+                format_args!($($arg)+),
+                err,
+                err.code()
+            );
+        }
+    };
+}
+
+// Longest error message I can find requires length of 419
+const BUF_SIZE: usize = 420;
+
+fn fmt_error(code: u32) -> Option<String> {
+    fmt_error_lang(code, default_language())
+}
+
+fn fmt_error_lang(code: u32, lang: u16) -> Option<String> {
+    lookup_message(code, |flags, source, code| {
+        fmt_message(flags, source, code, lang)
+    })
+}
+
+// Modules searched for COM and RPC HRESULTs that the system table lacks.
+const COM_MESSAGE_MODULES: [&str; 2] = ["ole32.dll", "oleaut32.dll"];
+
+// Loads a DLL as a data file for its message table. Returns null on failure;
+// otherwise the caller must free it with FreeLibrary.
+fn load_data_file(dll: &str) -> HMODULE {
+    let name: Vec<u16> = dll.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe { LoadLibraryExW(name.as_ptr(), NULL, LOAD_LIBRARY_AS_DATAFILE) }
+}
+
+// Finds the message for `code`, calling `format` for each place it may be
+// found until one succeeds: the system table, then any registered modules.
+// After that, an HRESULT wrapping a Win32 code gets that code's system
+// message, and a COM or RPC HRESULT is looked up in the OLE modules.
+//
+// Every way of formatting an Error's message goes through here, so they all
+// agree on which codes have a message.
+fn lookup_message<T>(
+    code: u32,
+    mut format: impl FnMut(u32, LPCVOID, u32) -> Option<T>,
+) -> Option<T> {
+    const SYSTEM: u32 = FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS;
+    const MODULE: u32 = FORMAT_MESSAGE_FROM_HMODULE | FORMAT_MESSAGE_IGNORE_INSERTS;
+    if let Some(found) = format(SYSTEM, NULL, code) {
+        return Some(found);
+    }
+    {
+        let modules = MESSAGE_MODULES.lock().unwrap_or_else(|e| e.into_inner());
+        if !modules.is_empty() {
+            // Keep the system table's failure as the last error, so callers report
+            // it rather than whatever the last module failed with.
+            let err = unsafe { GetLastError() };
+            let found = modules
+                .iter()
+                .find_map(|&module| format(MODULE, module as LPCVOID, code));
+            if found.is_some() {
+                return found;
+            }
+            unsafe { SetLastError(err) };
+        }
+    }
+    let hr = code as i32;
+    if hr >= 0 {
+        return None;
+    }
+    match (code >> 16) & 0x7FF {
+        // An HRESULT wrapping a Win32 code has that code's message.
+        FACILITY_WIN32 if hresult_from_win32(win32_from_hresult(hr)) == hr => {
+            format(SYSTEM, NULL, win32_from_hresult(hr))
+        }
+        FACILITY_RPC | FACILITY_DISPATCH | FACILITY_ITF => {
+            let err = unsafe { GetLastError() };
+            let found = COM_MESSAGE_MODULES.iter().find_map(|dll| {
+                let module = load_data_file(dll);
+                if module.is_null() {
+                    return None;
+                }
+                let found = format(MODULE, module as LPCVOID, code);
+                unsafe { FreeLibrary(module) };
+                found
+            });
+            if found.is_none() {
+                unsafe { SetLastError(err) };
+            }
+            found
+        }
+        _ => None,
+    }
+}
+
+/// Formats the message for each of `codes`, pushing one entry per code onto `out`.
+///
+/// Each entry is the same as [`Error::message`] would return. The default
+/// language is read once, rather than once per code.
+pub fn format_codes_into(codes: &[u32], out: &mut Vec<Option<String>>) {
+    let lang = default_language();
+    let mut buf = MaybeUninit::uninit();
+    out.reserve(codes.len());
+    out.extend(codes.iter().map(|&code| {
+        lookup_message(code, |flags, source, code| {
+            fmt_message_in(&mut buf, flags, source, code, lang)
+        })
+    }));
+}
+
+// Modules are stored as addresses, since HMODULE is not Send.
+static MESSAGE_MODULES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+// Counts registrations, so that caches can tell when they have gone stale.
+static MODULES_REGISTERED: AtomicU32 = AtomicU32::new(0);
+
+/// Registers a module whose message table is searched when the system table
+/// has no message for a code. Modules are searched in registration order.
+///
+/// The list is shared by all threads. The module must stay loaded for as long
+/// as errors may be formatted, which in practice means for the rest of the process.
+///
+/// With the `cache` feature, this also clears the cache behind
+/// [`Error::message_cached`], since codes cached without a message may now have one.
+pub fn register_message_module(module: HMODULE) {
+    MESSAGE_MODULES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(module as usize);
+    MODULES_REGISTERED.fetch_add(1, Ordering::Relaxed);
+    #[cfg(feature = "cache")]
+    CACHED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn fmt_module_error(module: HMODULE, code: u32, lang: u16) -> Option<String> {
+    const FLAGS: u32 = FORMAT_MESSAGE_FROM_HMODULE | FORMAT_MESSAGE_IGNORE_INSERTS;
+    fmt_message(FLAGS, module as _, code, lang)
+}
+
+// Test-only injection point: the number of upcoming fmt_message calls on this
+// thread that fail as if the code had no message.
+#[cfg(test)]
+thread_local! {
+    static FAILING_FORMATS: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+    static FORMAT_CALLS: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+}
+
+#[cfg(test)]
+fn with_failing_formats<T>(count: u32, f: impl FnOnce() -> T) -> T {
+    FAILING_FORMATS.with(|c| c.set(count));
+    let ret = f();
+    FAILING_FORMATS.with(|c| c.set(0));
+    ret
+}
+
+fn fmt_message(flags: u32, source: LPCVOID, code: u32, lang: u16) -> Option<String> {
+    #[cfg(test)]
+    {
+        FORMAT_CALLS.with(|c| c.set(c.get() + 1));
+        let fail = FAILING_FORMATS.with(|c| {
+            let count = c.get();
+            c.set(count.saturating_sub(1));
+            count > 0
+        });
+        if fail {
+            unsafe { SetLastError(sys::ERROR_MR_MID_NOT_FOUND) };
+            return None;
+        }
+    }
+    // The buffer lives on the stack and is never initialized up front, and the
+    // message is trimmed before it is converted, so the returned String is the
+    // only allocation.
+    fmt_message_in(&mut MaybeUninit::uninit(), flags, source, code, lang)
+}
+
+// Like fmt_message, but formats into a caller-provided buffer.
+fn fmt_message_in(
+    buf: &mut MaybeUninit<[u16; BUF_SIZE]>,
+    flags: u32,
+    source: LPCVOID,
+    code: u32,
+    lang: u16,
+) -> Option<String> {
+    let buf_ptr: *mut u16 = buf.as_mut_ptr().cast();
+    unsafe {
+        let len = FormatMessageW(
+            flags,
+            source, // source (module or fmt string)
+            code,   // msg id
+            lang as u32, // lang id
+            buf_ptr,
+            BUF_SIZE as u32,
+            NULL as _, // fmt arguments
+        );
+        debug_assert!(
+            len as usize <= BUF_SIZE,
+            "FormatMessageW reported {} chars for a buffer of {}",
+            len,
+            BUF_SIZE
+        );
+        // Never trust the reported length further than the buffer we own.
+        let len = (len as usize).min(BUF_SIZE);
+        if len == 0 {
+            None
+        } else {
+            let slice = std::slice::from_raw_parts(buf_ptr, len);
+            Some(String::from_utf16_lossy(trim_utf16(slice)))
+        }
+    }
+}
+
+// Formats a message with insert arguments. FormatMessage allocates the buffer,
+// since substituted messages have no upper bound on their length. Callers must
+// check the template with inserts_supplied first.
+fn fmt_message_args(
+    flags: u32,
+    source: LPCVOID,
+    code: u32,
+    lang: u16,
+    args: &[Insert],
+) -> Option<String> {
+    // Strings are passed as pointers to NUL-terminated copies, numbers by value.
+    let wide: Vec<Vec<u16>> = args
+        .iter()
+        .map(|arg| match arg {
+            Insert::Str(s) => s.encode_utf16().chain(Some(0)).collect(),
+            Insert::WStr(s) => s.iter().copied().chain(Some(0)).collect(),
+            Insert::U32(_) => Vec::new(),
+        })
+        .collect();
+    let values: Vec<usize> = args
+        .iter()
+        .zip(&wide)
+        .map(|(arg, wide)| match arg {
+            Insert::U32(n) => *n as usize,
+            _ => wide.as_ptr() as usize,
+        })
+        .collect();
+    let mut buf: *mut u16 = std::ptr::null_mut();
+    unsafe {
+        let len = FormatMessageW(
+            flags | FORMAT_MESSAGE_ALLOCATE_BUFFER | FORMAT_MESSAGE_ARGUMENT_ARRAY,
+            source,
+            code,
+            lang as u32,
+            &mut buf as *mut *mut u16 as _,
+            0,
+            values.as_ptr() as _,
+        );
+        let msg = if len == 0 || buf.is_null() {
+            None
+        } else {
+            let slice = std::slice::from_raw_parts(buf, len as usize);
+            Some(String::from_utf16_lossy(slice))
+        };
+        if !buf.is_null() {
+            LocalFree(buf as _);
+        }
+        msg
+    }
+}
+
+// Returns the length of a message without a fixed-size buffer, by letting
+// FormatMessage allocate one.
+fn fmt_message_len(flags: u32, source: LPCVOID, code: u32) -> Option<usize> {
+    let mut buf: *mut u16 = std::ptr::null_mut();
+    unsafe {
+        let len = FormatMessageW(
+            flags | FORMAT_MESSAGE_ALLOCATE_BUFFER,
+            source,
+            code,
+            default_language() as u32,
+            &mut buf as *mut *mut u16 as _,
+            0,
+            NULL as _,
+        );
+        if !buf.is_null() {
+            LocalFree(buf as _);
+        }
+        if len == 0 {
+            None
+        } else {
+            Some(len as usize)
+        }
+    }
+}
+
+fn fmt_error_into(code: u32, buf: &mut [u16]) -> Result<usize, usize> {
+    let size = buf.len().min(u32::MAX as usize);
+    let found = lookup_message(code, |flags, source, code| {
+        let len = unsafe {
+            FormatMessageW(
+                flags,
+                source,
+                code,
+                default_language() as u32,
+                buf.as_mut_ptr(),
+                size as u32,
+                NULL as _,
+            )
+        };
+        let len = len as usize;
+        if len != 0 {
+            return Some(Ok(len.min(size)));
+        }
+        if last_error().code() != ERROR_INSUFFICIENT_BUFFER && size != 0 {
+            return None;
+        }
+        // Room for the terminating NUL is needed as well.
+        fmt_message_len(flags, source, code).map(|len| Err(len + 1))
+    });
+    found.unwrap_or(Ok(0))
+}
+
+impl Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Error")
+            .field("code", &self.code)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_message(f, self.code, default_language())
+    }
+}
+
+// Writes the message for `code` in `lang`, with a fallback describing why
+// formatting failed.
+//
+// There is exactly one level of fallback: the failure code is formatted once,
+// and if that fails too only its number is shown. This never recurses, so it
+// takes a bounded number of FormatMessage calls whatever the error state.
+fn write_message(f: &mut fmt::Formatter, code: u32, lang: u16) -> fmt::Result {
+    if let Some(s) = fmt_error_lang(code, lang) {
+        write!(f, "{}", s)
+    } else {
+        // This branch should never happen unless the
+        // error code is not a valid Windows message.
+        let fmt_err = last_error().code();
+        if let Some(s) = fmt_error_lang(fmt_err, lang) {
+            write!(
+                f,
+                "Error code {} (could not format due to internal error: {} - {})",
+                code, fmt_err, s
+            )
+        } else {
+            write!(
+                f,
+                "Error code {} (could not format due to internal error code: {})",
+                code, fmt_err
+            )
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// An error that carries a Windows error code.
+pub trait WinError {
+    /// Returns the Windows error code.
+    fn code(&self) -> u32;
+}
+impl WinError for Error {
+    fn code(&self) -> u32 {
+        self.code
+    }
+}
+
+/// Returns the Windows error code of a type-erased error, if it is an [`Error`].
+pub fn downcast_win_code(err: &(dyn std::error::Error + 'static)) -> Option<u32> {
+    err.downcast_ref::<Error>().map(|e| e.code())
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        Self::from_raw_os_error(e.code() as i32)
+    }
+}
+
+/// An [`Error`] together with the error it was created from.
+///
+/// [`Error`] itself stays a 4 byte `Copy` value, so anything extra it needs to
+/// carry lives here instead.
+#[derive(Debug)]
+pub struct RichError {
+    error: Error,
+    source: Option<io::Error>,
+    context: Option<String>,
+}
+impl RichError {
+    /// Returns the Windows error.
+    pub fn error(&self) -> Error {
+        self.error
+    }
+    /// Returns the error code.
+    pub fn code(&self) -> u32 {
+        self.error.code()
+    }
+}
+impl Display for RichError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(context) = &self.context {
+            write!(f, "{}: ", context)?;
+        }
+        match &self.source {
+            // Code 1 is a stand-in here, so its message would say nothing useful.
+            Some(source) if source.raw_os_error().is_none() => Display::fmt(source, f),
+            _ => Display::fmt(&self.error, f),
+        }
+    }
+}
+impl std::error::Error for RichError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_ref().map(|e| e as _)
+    }
+}
+
+/// An [`Error`] with its formatting options captured, returned by [`Error::lazy`].
+///
+/// Nothing is formatted until the value is displayed, so it is cheap to build
+/// one for a message that may never be logged.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct LazyError {
+    error: Error,
+    language: u16,
+}
+impl LazyError {
+    /// Returns the Windows error.
+    pub fn error(self) -> Error {
+        self.error
+    }
+    /// Returns the language the message will be formatted in.
+    pub fn language(self) -> u16 {
+        self.language
+    }
+}
+impl Display for LazyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_message(f, self.error.code, self.language)
+    }
+}
+impl std::error::Error for LazyError {}
+
+/// Displays just the code of an [`Error`], returned by [`Error::code_display`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct CodeDisplay(Error);
+impl Display for CodeDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "0x{:08X}", self.0.code)
+        } else {
+            write!(f, "{}", self.0.code)
+        }
+    }
+}
+
+/// An [`Error`] with a link to more help, returned by [`Error::with_help_url`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct HelpfulError {
+    error: Error,
+    url: String,
+}
+impl HelpfulError {
+    /// Returns the Windows error.
+    pub fn error(&self) -> Error {
+        self.error
+    }
+    /// Returns the help link.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+impl Display for HelpfulError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (see {})", self.error, self.url)
+    }
+}
+impl std::error::Error for HelpfulError {}
+
+// Formatted messages keyed by (code, language), with None for no message.
+type MessageCache = HashMap<(u32, u16), Option<Arc<str>>>;
+
+/// A reusable formatter that tries a prioritized list of languages and caches
+/// what it finds.
+///
+/// Each language is tried in order, then the neutral language (0), as with
+/// [`Error::message_first_available`]. Results, including codes that have no
+/// message in a language, are cached per code and language, so repeated errors
+/// are formatted only once. The cache is cleared when a module is added with
+/// [`register_message_module`].
+#[derive(Debug, Default)]
+pub struct MessageFormatter {
+    languages: Vec<u16>,
+    cache: Mutex<MessageCache>,
+    modules_seen: AtomicU32,
+}
+impl MessageFormatter {
+    /// Creates a formatter trying `languages` in order.
+    pub fn new(languages: Vec<u16>) -> Self {
+        MessageFormatter {
+            languages,
+            cache: Mutex::new(HashMap::new()),
+            modules_seen: AtomicU32::new(MODULES_REGISTERED.load(Ordering::Relaxed)),
+        }
+    }
+    /// Returns the languages tried, in order, before the neutral language.
+    pub fn languages(&self) -> &[u16] {
+        &self.languages
+    }
+    /// Returns the message for `err` in the first language that has one.
+    pub fn format(&self, err: Error) -> Option<Arc<str>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        // Codes cached without a message may have one in a newly added module.
+        let registered = MODULES_REGISTERED.load(Ordering::Relaxed);
+        if self.modules_seen.swap(registered, Ordering::Relaxed) != registered {
+            cache.clear();
+        }
+        self.languages
+            .iter()
+            .chain(std::iter::once(&0))
+            .find_map(|&lang| {
+                cache
+                    .entry((err.code, lang))
+                    .or_insert_with(|| fmt_error_lang(err.code, lang).map(Into::into))
+                    .clone()
+            })
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn test_fmt() {
+    let err = Error::with_code(0);
+    assert_eq!(format!("{}", err), "The operation completed successfully.");
+    let err = Error::with_code(1);
+    assert_eq!(format!("{}", err), "Incorrect function.");
+    let err = Error::with_code(52);
+    assert_eq!(format!("{}", err), "You were not connected because a duplicate name exists on the network. If joining a domain, go to System in Control Panel to change the computer name and try again. If joining a workgroup, choose another workgroup name.");
+    let err = Error::with_code(192);
+    assert_eq!(format!("{}", err), "The operating system cannot run %1.");
+    let err = Error::with_code(560);
+    assert_eq!(format!("{}", err), "Indicates that an attempt was made to assign protection to a file system file or directory and one of the SIDs in the security descriptor could not be translated into a GUID that could be stored by the file system.\r\nThis causes the protection attempt to fail, which may cause a file creation attempt to fail.");
+    let err = Error::with_code(609);
+    assert_eq!(format!("{}", err), "{Invalid DLL Entrypoint}\r\nThe dynamic link library %hs is not written correctly. The stack pointer has been left in an inconsistent state.\r\nThe entrypoint should be declared as WINAPI or STDCALL. Select YES to fail the DLL load. Select NO to continue execution. Selecting NO may cause the application to operate incorrectly.");
+    let err = Error::with_code(1290);
+    assert_eq!(format!("{}", err), "The service start failed since one or more services in the same process have an incompatible service SID type setting. A service with restricted service SID type can only coexist in the same process with other services with a restricted SID type. If the service SID type for this service was just configured, the hosting process must be restarted in order to start this service.");
+    // The longest error message (that I could find)
+    let err = Error::with_code(6719);
     assert_eq!(format!("{}", err), "The object specified could not be created or opened, because its associated TransactionManager is not online.  The TransactionManager must be brought fully Online by calling RecoverTransactionManager to recover to the end of its LogFile before objects in its Transaction or ResourceManager namespaces can be opened.  In addition, errors in writing records to its LogFile can cause a TransactionManager to go offline.");
     // A non-existant error code
     let err = Error::with_code(15999);
     assert_eq!(format!("{}", err), "Error code 15999 (could not format due to internal error: 317 - The system cannot find message text for message number 0x%1 in the message file for %2.)");
+    assert_eq!(format!("{:?}", err), "Error { code: 15999, message: None }");
+    let err = Error::with_code(1);
+    assert_eq!(format!("{:?}", err), "Error { code: 1, message: Some(\"Incorrect function.\") }");
+}
+
+#[test]
+fn test_code_hex() {
+    assert_eq!(Error::with_code(5).code_hex(), "0x00000005");
+    assert_eq!(Error::with_code(0).code_hex(), "0x00000000");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_win_result() {
+    assert_eq!(1.into_win_result(), Ok(()));
+    unsafe { SetLastError(5) };
+    assert_eq!(0.into_win_result(), Err(Error::with_code(5)));
+
+    let handle = 4 as HANDLE;
+    assert_eq!(handle.into_win_result(), Ok(handle));
+    unsafe { SetLastError(6) };
+    assert_eq!((NULL as HANDLE).into_win_result(), Err(Error::with_code(6)));
+    unsafe { SetLastError(2) };
+    assert_eq!(INVALID_HANDLE_VALUE.into_win_result(), Err(Error::with_code(2)));
+
+    assert_eq!(0u32.into_win_result(), Ok(()));
+    assert_eq!(5u32.into_win_result(), Err(Error::with_code(5)));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_os_version() {
+    let (major, _minor, build) = os_version();
+    assert!(major >= 6);
+    assert!(build > 0);
+}
+
+#[test]
+fn test_assert_win() {
+    assert_win!(1);
+    assert_win!(1, "with a message {}", 1);
+    assert_win!(0u32);
+}
+
+#[test]
+#[should_panic(expected = "assertion failed: 0: ")]
+fn test_assert_win_panics() {
+    unsafe { SetLastError(5) };
+    assert_win!(0);
+}
+
+#[test]
+#[should_panic(expected = "assertion failed: 5u32: closing 7: ")]
+fn test_assert_win_panics_with_message() {
+    assert_win!(5u32, "closing {}", 7);
+}
+
+#[test]
+fn test_ignore_codes() {
+    let results = vec![
+        Ok(1),
+        Err(Error::with_code(183)),
+        Ok(2),
+        Err(Error::with_code(5)),
+    ];
+    let kept: Vec<Result<i32>> = results.into_iter().ignore_codes(&[183]).collect();
+    assert_eq!(kept, vec![Ok(1), Ok(2), Err(Error::with_code(5))]);
+    let results: Vec<Result<i32>> = vec![Ok(1), Err(Error::with_code(183))];
+    assert_eq!(
+        results
+            .into_iter()
+            .ignore_codes(&[183])
+            .collect::<Result<Vec<_>>>(),
+        Ok(vec![1])
+    );
+}
+
+#[test]
+fn test_is_warning() {
+    // S_OK
+    assert!(!Error::with_code(0).is_warning());
+    // DRAGDROP_S_DROP
+    assert!(Error::with_code(0x0004_0100).is_warning());
+    // S_FALSE shares its value with ERROR_INVALID_FUNCTION.
+    assert!(!Error::with_code(1).is_warning());
+    // E_ACCESSDENIED
+    assert!(!Error::with_code(0x8007_0005).is_warning());
+}
+
+#[test]
+fn test_severity_label() {
+    // S_OK, DRAGDROP_S_DROP, ERROR_ACCESS_DENIED, E_ACCESSDENIED
+    assert_eq!(Error::with_code(0).severity_label(), Some("Success"));
+    assert_eq!(
+        Error::with_code(0x0004_0100).severity_label(),
+        Some("Warning")
+    );
+    assert_eq!(Error::with_code(5).severity_label(), Some("Error"));
+    assert_eq!(
+        Error::with_code(0x8007_0005).severity_label(),
+        Some("Error")
+    );
+    // STATUS_ACCESS_VIOLATION
+    assert_eq!(Error::with_code(0xC000_0005).severity_label(), None);
+}
+
+#[test]
+fn test_category() {
+    assert_eq!(Error::success().category(), Category::Success);
+    assert_eq!(Error::with_code(3).category(), Category::NotFound);
+    assert_eq!(Error::with_code(5).category(), Category::PermissionDenied);
+    assert_eq!(Error::with_code(112).category(), Category::Storage);
+    assert_eq!(Error::with_code(258).category(), Category::Timeout);
+    assert_eq!(Error::with_code(1223).category(), Category::Cancelled);
+    assert_eq!(Error::with_code(10054).category(), Category::Network);
+    assert_eq!(Error::with_code(15999).category(), Category::Other);
+}
+
+#[test]
+fn test_io_error_kind() {
+    assert_eq!(Error::with_code(2).io_error_kind(), io::ErrorKind::NotFound);
+    assert_eq!(
+        Error::with_code(183).io_error_kind(),
+        io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(
+        Error::with_code(10061).io_error_kind(),
+        io::ErrorKind::ConnectionRefused
+    );
+    assert_eq!(
+        Error::with_code(15999).io_error_kind(),
+        io::ErrorKind::Other
+    );
+    #[cfg(windows)]
+    for &code in &[2, 5, 87, 109, 183, 258, 995, 10035, 10054] {
+        let err = Error::with_code(code);
+        assert_eq!(err.io_error_kind(), io::Error::from(err).kind());
+    }
+}
+
+#[test]
+fn test_into_io_with_message() {
+    let err = Error::with_code(5);
+    let io_err = err.into_io_with_message();
+    assert_eq!(io_err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(!io_err.to_string().is_empty());
+    assert_eq!(io_err.to_string(), err.to_string());
+    assert_eq!(downcast_win_code(io_err.get_ref().unwrap()), Some(5));
+}
+
+#[test]
+fn test_describe() {
+    let desc = Error::with_code(5).describe();
+    assert_eq!(desc.code, 5);
+    assert_eq!(desc.hex, "0x00000005");
+    assert_eq!(desc.symbol, Some("ERROR_ACCESS_DENIED"));
+    assert_eq!(desc.category, Category::PermissionDenied);
+    assert_eq!(desc.io_kind, io::ErrorKind::PermissionDenied);
+    assert_eq!(desc.message, Error::with_code(5).message());
+    #[cfg(windows)]
+    assert_eq!(desc.message.as_deref(), Some("Access is denied."));
+}
+
+#[test]
+fn test_cmp_by_severity() {
+    let mut errors = [0, 5, 0x0004_0001, 0x8007_0005, 1, 0x8000_4003, 0].map(Error::with_code);
+    errors.sort_by(|a, b| a.cmp_by_severity(*b));
+    let codes: Vec<u32> = errors.iter().map(|e| e.code()).collect();
+    assert_eq!(codes, [1, 5, 0x8000_4003, 0x8007_0005, 0x0004_0001, 0, 0]);
+    // The groups follow severity_label.
+    let rank = |e: &Error| match e.severity_label() {
+        Some("Error") => 0,
+        Some("Warning") => 1,
+        _ => 2,
+    };
+    assert!(errors.windows(2).all(|w| rank(&w[0]) <= rank(&w[1])));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_as_fields() {
+    assert_eq!(Error::with_code(1).as_fields(), (1, Some("Incorrect function.".to_string())));
+    assert_eq!(Error::with_code(15999).as_fields(), (15999, None));
+}
+
+#[test]
+fn test_indent_lines() {
+    assert_eq!(
+        indent_lines("one\r\ntwo\nthree", 2),
+        "one\r\n  two\n  three"
+    );
+    assert_eq!(indent_lines("one", 4), "one");
+    assert_eq!(indent_lines("one\r\ntwo", 0), "one\r\ntwo");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_indented() {
+    let msg = Error::with_code(560).message_indented(4).unwrap();
+    let mut lines = msg.lines();
+    assert!(lines.next().unwrap().starts_with("Indicates that"));
+    assert!(lines.next().unwrap().starts_with("    This causes"));
+    assert_eq!(lines.next(), None);
+    assert_eq!(Error::with_code(15999).message_indented(4), None);
+}
+
+#[test]
+fn test_json_string() {
+    assert_eq!(
+        json_string("say \"hi\"\r\nC:\\dir\u{1}"),
+        r#""say \"hi\"\r\nC:\\dir\u0001""#
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn test_to_json() {
+    let err = Error::with_code(5);
+    assert_eq!(err.to_json(), r#"{"code":5,"message":"Access is denied."}"#);
+    let json = Error::with_code(560).to_json();
+    assert!(json.contains(r#"could be stored by the file system.\r\nThis causes"#));
+    assert!(!json.contains('\r'));
+    let err = Error::with_code(15999);
+    assert_eq!(err.to_json(), r#"{"code":15999,"message":null}"#);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_or_code() {
+    assert_eq!(Error::with_code(1).message_or_code(), "Incorrect function.");
+    assert_eq!(Error::with_code(15999).message_or_code(), "15999");
+}
+
+#[test]
+fn test_html_escape() {
+    assert_eq!(
+        html_escape("a < b & \"c\" > d\r\nnext"),
+        "a &lt; b &amp; &quot;c&quot; &gt; d<br>next"
+    );
+    assert_eq!(html_escape("can't"), "can&#39;t");
+    assert_eq!(html_escape("plain"), "plain");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_html() {
+    let msg = Error::with_code(560).message_html().unwrap();
+    assert!(msg.contains("by the file system.<br>This causes"));
+    assert!(!msg.contains('\r'));
+    assert_eq!(Error::with_code(15999).message_html(), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_lf() {
+    let msg = Error::with_code(560).message_lf().unwrap();
+    assert!(!msg.contains('\r'));
+    assert!(msg.contains("by the file system.\nThis causes"));
+    assert!(!msg.ends_with('\n'));
+    assert_eq!(Error::with_code(15999).message_lf(), None);
+}
+
+#[test]
+fn test_message_display() {
+    let unknown = Error::with_code(15999).message_display();
+    assert_eq!(unknown, "Unknown error (code 15999)");
+    let known = Error::with_code(5).message_display();
+    assert!(!known.is_empty());
+    #[cfg(windows)]
+    assert_eq!(known, "Access is denied.");
+}
+
+#[test]
+fn test_normalize_message() {
+    assert_eq!(normalize_message("  One\r\n two.  "), "One two");
+    assert_eq!(normalize_message("Done!?"), "Done");
+    assert_eq!(normalize_message("v1.0 . "), "v1.0");
+}
+
+#[test]
+fn test_message_matches() {
+    assert!(!Error::with_code(15999).message_matches(""));
+    #[cfg(windows)]
+    {
+        let err = Error::with_code(0);
+        assert!(err.message_matches("The operation completed successfully"));
+        assert!(err.message_matches("The  operation completed\nsuccessfully."));
+        assert!(!err.message_matches("The operation completed"));
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_summary() {
+    let err = Error::with_code(609);
+    assert_eq!(err.message_summary().as_deref(), Some("{Invalid DLL Entrypoint}"));
+    assert!(err.message().unwrap().starts_with("{Invalid DLL Entrypoint}\r\nThe dynamic link library"));
+    let err = Error::with_code(1);
+    assert_eq!(err.message_summary().as_deref(), Some("Incorrect function."));
+    assert_eq!(Error::with_code(15999).message_summary(), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_without_brace_header() {
+    let msg = Error::with_code(609).message_without_brace_header().unwrap();
+    assert!(msg.starts_with("The dynamic link library %hs is not written correctly."));
+    let err = Error::with_code(1);
+    assert_eq!(err.message_without_brace_header(), err.message());
+}
+
+#[test]
+fn test_split_title() {
+    assert_eq!(split_title("{Title}\r\nBody"), ("{Title}", "Body"));
+    assert_eq!(split_title("First.\r\nSecond."), ("First.", "Second."));
+    assert_eq!(split_title("Only line."), ("Only line.", ""));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_title_and_body() {
+    let (title, body) = Error::with_code(609).title_and_body().unwrap();
+    assert_eq!(title, "{Invalid DLL Entrypoint}");
+    assert!(body.starts_with("The dynamic link library %hs"));
+    let (title, body) = Error::with_code(5).title_and_body().unwrap();
+    assert_eq!(title, "Access is denied.");
+    assert_eq!(body, "");
+    assert_eq!(Error::with_code(15999).title_and_body(), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_with_inserts_ignored() {
+    let err = Error::with_code(192);
+    assert_eq!(
+        err.message_with_inserts_ignored(true).as_deref(),
+        Some("The operating system cannot run %1.")
+    );
+    assert_eq!(err.message_with_inserts_ignored(false), None);
+    let err = Error::with_code(1);
+    assert_eq!(err.message_with_inserts_ignored(false).as_deref(), Some("Incorrect function."));
+}
+
+#[test]
+fn test_format_codes_into() {
+    let codes = [0, 1, 2, 15999];
+    let mut out = vec![None];
+    format_codes_into(&codes, &mut out);
+    assert_eq!(out.len(), 5);
+    for (&code, msg) in codes.iter().zip(&out[1..]) {
+        assert_eq!(*msg, Error::with_code(code).message());
+    }
+    #[cfg(windows)]
+    {
+        assert_eq!(
+            out[1].as_deref(),
+            Some("The operation completed successfully.")
+        );
+        assert_eq!(out[4], None);
+    }
+}
+
+#[test]
+fn test_summarize() {
+    let errors: Vec<Error> = [5, 2, 5, 3, 2, 5, 1].iter().map(|&c| Error::with_code(c)).collect();
+    let summary = summarize(&errors);
+    let expected = vec![
+        (Error::with_code(5), 3),
+        (Error::with_code(2), 2),
+        (Error::with_code(1), 1),
+        (Error::with_code(3), 1),
+    ];
+    assert_eq!(summary, expected);
+    assert!(summarize(&[]).is_empty());
+}
+
+#[test]
+fn test_snapshot() {
+    fn assert_send<T: Send + 'static>(_: &T) {}
+    unsafe { SetLastError(5) };
+    let err = Error::snapshot();
+    assert_send(&err);
+    #[cfg(windows)]
+    assert_eq!(err, Error::with_code(5));
+    let handle = std::thread::spawn(move || err.code());
+    assert_eq!(handle.join().unwrap(), err.code());
+}
+
+#[cfg(windows)]
+#[test]
+fn test_last_with_timestamp() {
+    let before = SystemTime::now();
+    unsafe { SetLastError(5) };
+    let (err, time) = Error::last_with_timestamp();
+    assert_eq!(err, Error::with_code(5));
+    assert!(time >= before);
+    assert!(time.elapsed().unwrap() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_probe() {
+    unsafe { SetLastError(5) };
+    assert_eq!(probe(|| {}), None);
+    #[cfg(windows)]
+    assert_eq!(
+        probe(|| unsafe { SetLastError(6) }),
+        Some(Error::with_code(6))
+    );
+}
+
+#[test]
+fn test_handle_or_last() {
+    // The pseudo-handle returned by GetCurrentThread.
+    let thread = -2isize as HANDLE;
+    assert_eq!(handle_or_last(thread), Ok(thread));
+    unsafe { SetLastError(6) };
+    let err = handle_or_last(INVALID_HANDLE_VALUE).unwrap_err();
+    #[cfg(windows)]
+    assert_eq!(err, Error::with_code(6));
+    let _ = err;
+    assert!(handle_or_last(NULL as HANDLE).is_err());
+}
+
+#[test]
+fn test_last_error_after() {
+    let ok: std::result::Result<(), ()> = Ok(());
+    assert_eq!(last_error_after(&ok), None);
+    #[cfg(windows)]
+    {
+        let result = std::fs::File::open("this file does not exist.txt");
+        let err = last_error_after(&result).unwrap();
+        assert_ne!(err.code(), 0);
+        assert_eq!(Some(err.code() as i32), result.unwrap_err().raw_os_error());
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn test_try_all() {
+    use std::cell::Cell;
+
+    let ran = Cell::new(0);
+    let ok = || {
+        ran.set(ran.get() + 1);
+        true
+    };
+    let fail = || {
+        ran.set(ran.get() + 1);
+        unsafe { SetLastError(6) };
+        false
+    };
+    assert_eq!(try_all(&[&ok, &fail, &ok]), Err(Error::with_code(6)));
+    assert_eq!(ran.get(), 2);
+    assert_eq!(try_all(&[&ok, &ok]), Ok(()));
+    assert_eq!(try_all(&[]), Ok(()));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_from_wait_result() {
+    assert_eq!(Error::from_wait_result(0), None);
+    // WAIT_OBJECT_0 + 3, WAIT_ABANDONED_0, WAIT_ABANDONED_0 + 5, WAIT_IO_COMPLETION
+    assert_eq!(Error::from_wait_result(3), None);
+    assert_eq!(Error::from_wait_result(0x80), None);
+    assert_eq!(Error::from_wait_result(0x85), None);
+    assert_eq!(Error::from_wait_result(0xC0), None);
+    assert_eq!(Error::from_wait_result(258), Some(Error::with_code(258)));
+    unsafe { SetLastError(6) };
+    assert_eq!(Error::from_wait_result(0xFFFF_FFFF), Some(Error::with_code(6)));
+}
+
+#[test]
+fn test_symbol_name() {
+    assert!(SYMBOLS.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(Error::with_code(0).symbol_name(), Some("ERROR_SUCCESS"));
+    assert_eq!(Error::with_code(5).symbol_name(), Some("ERROR_ACCESS_DENIED"));
+    assert_eq!(Error::with_code(15999).symbol_name(), None);
+}
+
+#[test]
+fn test_hresult_conversions() {
+    const _: () = assert!(hresult_from_win32(5) == 0x8007_0005_u32 as i32);
+    const _: () = assert!(hresult_from_win32(0) == 0);
+    const _: () = assert!(win32_from_hresult(0x8007_0005_u32 as i32) == 5);
+    const _: () = assert!(win32_from_hresult(0x8007_0002_u32 as i32) == 2);
+    assert_eq!(
+        from_hresult(hresult_from_win32(1314)),
+        Error::with_code(1314)
+    );
+}
+
+#[test]
+fn test_code_from_symbol() {
+    assert_eq!(code_from_symbol("ERROR_ACCESS_DENIED"), Some(5));
+    assert_eq!(code_from_symbol("ERROR_FILE_NOT_FOUND"), Some(2));
+    assert_eq!(code_from_symbol("ERROR_NOT_A_REAL_ERROR"), None);
+
+    assert_eq!("ERROR_FILE_NOT_FOUND".parse(), Ok(Error::with_code(2)));
+    assert_eq!("5".parse(), Ok(Error::with_code(5)));
+    assert_eq!("0x80070005".parse(), Ok(Error::with_code(0x8007_0005)));
+    assert_eq!("nonsense".parse::<Error>(), Err(ParseError(())));
+}
+
+#[test]
+fn test_from_io_with_source() {
+    use std::error::Error as _;
+
+    let err = Error::from_io_with_source(io::Error::from_raw_os_error(5));
+    assert_eq!(err.code(), 5);
+    let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(source.raw_os_error(), Some(5));
+    let err = Error::from_io_with_source(io::Error::other("custom"));
+    assert_eq!(err.code(), 1);
+    assert_eq!(err.to_string(), "custom");
+    let err = Error::wrap_io(io::Error::other("custom"), "opening config");
+    assert_eq!(err.to_string(), "opening config: custom");
+}
+
+#[test]
+fn test_wrap_io() {
+    let err = Error::wrap_io(io::Error::from_raw_os_error(5), "opening config");
+    assert_eq!(err.code(), 5);
+    assert_eq!(
+        err.to_string(),
+        format!("opening config: {}", Error::with_code(5))
+    );
+    #[cfg(windows)]
+    {
+        use std::error::Error as _;
+
+        let io_err = std::fs::File::open("this file does not exist.txt").unwrap_err();
+        let code = io_err.raw_os_error();
+        let err = Error::wrap_io(io_err, "opening input");
+        assert!(err.to_string().starts_with("opening input: "));
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.raw_os_error(), code);
+        assert_eq!(Some(err.code() as i32), code);
+    }
+}
+
+#[test]
+fn test_display_string() {
+    let err = Error::with_code(15999);
+    assert_eq!(err.display_string(DisplayOpts::default()), "Unknown error");
+    let opts = DisplayOpts {
+        include_code: true,
+        include_hex: true,
+        include_symbol: true,
+        ..DisplayOpts::default()
+    };
+    assert_eq!(err.display_string(opts), "15999, 0x00003E7F: Unknown error");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_display_string_windows() {
+    let opts = DisplayOpts {
+        include_code: true,
+        single_line: true,
+        ..DisplayOpts::default()
+    };
+    let msg = Error::with_code(560).display_string(opts);
+    assert!(msg.starts_with("560: Indicates that"));
+    assert!(!msg.contains('\n'));
+    let opts = DisplayOpts {
+        include_hex: true,
+        include_symbol: true,
+        language: Some(0x0409),
+        ..DisplayOpts::default()
+    };
+    let err = Error::with_code(5);
+    assert_eq!(err.display_string(opts), err.to_full_string());
+}
+
+#[cfg(windows)]
+#[test]
+fn test_to_full_string() {
+    let err = Error::with_code(5);
+    assert_eq!(err.to_full_string(), "ERROR_ACCESS_DENIED (0x00000005): Access is denied.");
+    let err = Error::with_code(15999);
+    assert_eq!(err.to_full_string(), "0x00003E7F: Unknown error");
+}
+
+#[test]
+fn test_success() {
+    assert!(Error::default().is_success());
+    assert_eq!(Error::success().code(), 0);
+    assert!(!Error::with_code(5).is_success());
+}
+
+#[test]
+fn test_wrapped_hresult_formats_consistently() {
+    // E_ACCESSDENIED, which wraps ERROR_ACCESS_DENIED.
+    let err = Error::from_com(0x8007_0005_u32 as i32);
+    let mut out = Vec::new();
+    format_codes_into(&[err.code()], &mut out);
+    assert_eq!(out, [err.message()]);
+    let mut buf = [0u16; 64];
+    let len = err.format_into_buf(&mut buf).unwrap();
+    let message = String::from_utf16(&buf[..len]).unwrap();
+    assert_eq!(message.trim(), err.message().unwrap_or_default());
+    assert_eq!(err.format_full(&[], 0), err.message());
+    assert_eq!(err.format_with_inserts(&[]), err.message());
+    assert_eq!(err.message_with_inserts_ignored(true), err.message());
+    #[cfg(windows)]
+    assert_eq!(out[0].as_deref(), Some("Access is denied."));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_format_into_buf() {
+    let err = Error::with_code(1);
+    let expected: Vec<u16> = "Incorrect function.\r\n".encode_utf16().collect();
+    let mut small = [0u16; 4];
+    assert_eq!(err.format_into_buf(&mut small), Err(expected.len() + 1));
+    let mut buf = vec![0u16; expected.len() + 1];
+    assert_eq!(err.format_into_buf(&mut buf), Ok(expected.len()));
+    assert_eq!(&buf[..expected.len()], &expected[..]);
+    assert_eq!(Error::with_code(15999).format_into_buf(&mut buf), Ok(0));
+}
+
+#[cfg(feature = "unicode-width")]
+#[test]
+fn test_display_width() {
+    assert_eq!(Error::with_code(15999).display_width(), None);
+    #[cfg(windows)]
+    {
+        let err = Error::with_code(5);
+        let summary = err.message_summary().unwrap();
+        assert_eq!(err.display_width(), Some(summary.chars().count()));
+    }
+}
+
+#[cfg(windows)]
+#[cfg(feature = "anyhow")]
+#[test]
+fn test_into_anyhow() {
+    let err = Error::with_code(5).into_anyhow();
+    assert_eq!(
+        err.to_string(),
+        "ERROR_ACCESS_DENIED (0x00000005): Access is denied."
+    );
+    assert_eq!(
+        format!("{:#}", err),
+        "ERROR_ACCESS_DENIED (0x00000005): Access is denied.: Access is denied."
+    );
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::with_code(5)));
+}
+
+#[cfg(windows)]
+#[cfg(feature = "crt")]
+#[test]
+fn test_crt_errno() {
+    // ENOENT shares its value with ERROR_FILE_NOT_FOUND, but not its message.
+    let err = Error::from_crt_errno(2);
+    assert_eq!(err.errno(), 2);
+    assert_eq!(format!("{}", err), "No such file or directory");
+    assert_eq!(
+        format!("{}", Error::with_code(2)),
+        "The system cannot find the file specified."
+    );
+}
+
+#[test]
+fn test_in_range() {
+    let err = Error::with_code(5);
+    assert!(err.in_range(ErrorBand::System));
+    assert!(!err.in_range(ErrorBand::Rpc));
+    // RPC_S_SERVER_UNAVAILABLE
+    let err = Error::with_code(1722);
+    assert!(err.in_range(ErrorBand::Rpc));
+    assert!(err.in_range(ErrorBand::System));
+    // WSAECONNRESET
+    assert!(Error::with_code(10054).in_range(ErrorBand::Winsock));
+    // ERROR_NO_SUCH_DEVINST
+    let err = Error::with_code(0xE000_020B);
+    assert!(err.in_range(ErrorBand::Setup));
+    assert!(err.in_range(ErrorBand::Custom));
+    assert!(!err.in_range(ErrorBand::System));
+    assert!(Error::with_code(0x2000_0001).in_range(ErrorBand::Custom));
+}
+
+#[test]
+fn test_fmt_fallback_bounded() {
+    let calls = || FORMAT_CALLS.with(|c| c.get());
+    let before = calls();
+    let s = with_failing_formats(u32::MAX, || format!("{}", Error::with_code(5)));
+    let used = calls() - before;
+    assert!(s.starts_with("Error code 5 (could not format due to internal error code: "));
+    #[cfg(windows)]
+    assert_eq!(
+        s,
+        "Error code 5 (could not format due to internal error code: 317)"
+    );
+    // The code and the failure code, each tried once against the system table.
+    assert!(used <= 2);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_fmt_fallback() {
+    let err = Error::with_code(5);
+    let s = with_failing_formats(1, || format!("{}", err));
+    assert_eq!(s, "Error code 5 (could not format due to internal error: 317 - The system cannot find message text for message number 0x%1 in the message file for %2.)");
+    let s = with_failing_formats(u32::MAX, || format!("{}", err));
+    assert_eq!(s, "Error code 5 (could not format due to internal error code: 317)");
+    let s = with_failing_formats(u32::MAX, || format!("{:?}", err));
+    assert_eq!(s, "Error { code: 5, message: None }");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_insert_spec() {
+    let spec = Error::with_code(192).insert_spec();
+    assert_eq!(spec, vec![InsertKind { index: 1, format: None }]);
+    assert_eq!(Error::with_code(609).insert_spec(), vec![]);
+    assert_eq!(Error::with_code(0).insert_spec(), vec![]);
+}
+
+#[test]
+fn test_inserts_supplied() {
+    use Insert::*;
+    assert!(inserts_supplied("no inserts", &[]));
+    assert!(inserts_supplied("%1 and %2!s!", &[Str("a"), WStr(&[98])]));
+    assert!(!inserts_supplied("%1 and %2", &[Str("a")]));
+    assert!(!inserts_supplied("%1!d!", &[Str("a")]));
+    assert!(inserts_supplied("%1!d! and %2!X!", &[U32(1), U32(2)]));
+    assert!(!inserts_supplied("%1", &[U32(1)]));
+    assert!(inserts_supplied("100%% done%n", &[]));
+}
+
+#[test]
+fn test_substitute_inserts() {
+    let mut named = HashMap::new();
+    named.insert(1, "app.exe".to_string());
+    named.insert(2, "C:\\".to_string());
+    assert_eq!(
+        substitute_inserts("Run %1 in %2!s! with %3, %hs, 100%%", &named),
+        "Run app.exe in C:\\ with %3, %hs, 100%"
+    );
+    assert_eq!(substitute_inserts("100%%1", &named), "100%1");
+    assert_eq!(substitute_inserts("%12 and %", &named), "%12 and %");
+    named.insert(12, "twelve".to_string());
+    assert_eq!(substitute_inserts("%1234", &named), "twelve34");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_format_named() {
+    let mut named = HashMap::new();
+    named.insert(1, "setup.exe".to_string());
+    assert_eq!(
+        Error::with_code(192).format_named(&named).as_deref(),
+        Some("The operating system cannot run setup.exe.")
+    );
+    assert_eq!(
+        Error::with_code(192).format_named(&HashMap::new()),
+        Error::with_code(192).message()
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn test_fmt_message_args() {
+    const FORMAT_MESSAGE_FROM_STRING: u32 = 0x0400;
+    let template: Vec<u16> = "%1 has %2!d! items (%3)\0".encode_utf16().collect();
+    let name: Vec<u16> = "list".encode_utf16().collect();
+    let args = [Insert::WStr(&name), Insert::U32(42), Insert::Str("ok")];
+    let msg = fmt_message_args(
+        FORMAT_MESSAGE_FROM_STRING,
+        template.as_ptr() as _,
+        0,
+        0,
+        &args,
+    );
+    assert_eq!(msg.as_deref(), Some("list has 42 items (ok)"));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_format_full() {
+    let err = Error::with_code(192);
+    let msg = err.format_full(&["foo.exe"], 20).unwrap();
+    assert!(msg.contains("foo.exe"));
+    assert!(!msg.contains("%1"));
+    assert!(msg.lines().count() > 1);
+    assert!(msg
+        .lines()
+        .all(|line| line.trim_end().chars().count() <= 20));
+    assert_eq!(
+        err.format_full(&["foo.exe"], 0).unwrap(),
+        "The operating system cannot run foo.exe."
+    );
+    assert_eq!(err.format_full(&[], 0), None);
+    assert_eq!(
+        err.format_with_inserts(&[Insert::Str("a.exe")]).as_deref(),
+        Some("The operating system cannot run a.exe.")
+    );
+    assert_eq!(err.format_with_inserts(&[Insert::U32(1)]), None);
+    assert_eq!(Error::with_code(15999).format_full(&[], 0), None);
+}
+
+#[test]
+fn test_parse_inserts() {
+    let spec = parse_inserts("%1 of %2!d! is 100%% done%n%12!ws!%.");
+    assert_eq!(
+        spec,
+        vec![
+            InsertKind { index: 1, format: None },
+            InsertKind { index: 2, format: Some("d".to_string()) },
+            InsertKind { index: 12, format: Some("ws".to_string()) },
+        ]
+    );
+    let spec = parse_inserts("%123 and %99999999999");
+    let indices: Vec<u32> = spec.iter().map(|i| i.index).collect();
+    assert_eq!(indices, [12, 99]);
+    assert!(!inserts_supplied("%99999999999", &[]));
+}
+
+#[test]
+fn test_downcast_win_code() {
+    let err: Box<dyn std::error::Error> = Box::new(Error::with_code(5));
+    assert_eq!(downcast_win_code(err.as_ref()), Some(5));
+    let err: Box<dyn std::error::Error> = "not a windows error".into();
+    assert_eq!(downcast_win_code(err.as_ref()), None);
+    let err: &dyn WinError = &Error::with_code(6);
+    assert_eq!(err.code(), 6);
+}
+
+#[test]
+fn test_expect_success() {
+    Error::with_code(0).expect_success("should not panic");
+}
+
+#[cfg(windows)]
+#[test]
+#[should_panic(expected = "opening config: Access is denied. (code 5)")]
+fn test_expect_success_panics() {
+    Error::with_code(5).expect_success("opening config");
+}
+
+#[test]
+fn test_as_failure() {
+    assert_eq!(Error::with_code(0).as_failure(), None);
+    let failure = Error::with_code(5).as_failure().unwrap();
+    assert_eq!(failure.code(), 5);
+    assert_eq!(Error::from(failure), Error::with_code(5));
+}
+
+#[test]
+fn test_from_bool_and_code() {
+    assert_eq!(Error::from_bool_and_code(true, 5), None);
+    assert_eq!(
+        Error::from_bool_and_code(false, 5),
+        Some(Error::with_code(5))
+    );
+}
+
+#[test]
+fn test_from_com() {
+    let err = Error::from_com(0x8007_0005_u32 as i32);
+    assert_eq!(err.code(), 0x8007_0005);
+    assert!(err.matches_hresult(0x8007_0005_u32 as i32));
+    #[cfg(windows)]
+    {
+        assert_eq!(err.message().as_deref(), Some("Access is denied."));
+        // E_NOINTERFACE
+        assert!(Error::from_com(0x8000_4002_u32 as i32).message().is_some());
+    }
+}
+
+#[test]
+fn test_from_registry() {
+    assert_eq!(Error::from_registry(0), None);
+    assert_eq!(Error::from_registry(2), Some(Error::with_code(2)));
+    assert_eq!(
+        Error::from_registry(-1),
+        Some(Error::with_code(0xFFFF_FFFF))
+    );
+}
+
+#[test]
+fn test_from_shared_code() {
+    let lazy = Error::from_shared_code(5, 0x0409);
+    assert_eq!(lazy.error(), Error::with_code(5));
+    assert_eq!(lazy.language(), 0x0409);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_from_shared_code_ignores_last_error() {
+    let captured = Error::from_shared_code(5, 0x0409);
+    let expected = captured.to_string();
+    unsafe { SetLastError(1234) };
+    assert_eq!(captured.to_string(), expected);
+    assert_eq!(expected, "Access is denied.");
+    unsafe { SetLastError(87) };
+    assert_eq!(
+        captured.error().message().as_deref(),
+        Some("Access is denied.")
+    );
+}
+
+#[test]
+fn test_from_out_ptr() {
+    let code: u32 = 5;
+    assert_eq!(
+        unsafe { Error::from_out_ptr(&code) },
+        Some(Error::with_code(5))
+    );
+    assert_eq!(unsafe { Error::from_out_ptr(std::ptr::null()) }, None);
+}
+
+#[test]
+fn test_code_display() {
+    let code = Error::with_code(5).code_display();
+    assert_eq!(format!("{}", code), "5");
+    assert_eq!(format!("{:#}", code), "0x00000005");
+    assert_eq!(
+        format!("{:#}", Error::with_code(0x8007_0005).code_display()),
+        "0x80070005"
+    );
+}
+
+#[test]
+fn test_with_help_url() {
+    let err = Error::with_code(5);
+    let helpful = err.with_help_url("https://example.com/errors/");
+    assert_eq!(helpful.error(), err);
+    assert_eq!(helpful.url(), "https://example.com/errors/0x00000005");
+    let text = helpful.to_string();
+    assert!(text.starts_with(&err.to_string()));
+    assert!(text.ends_with(" (see https://example.com/errors/0x00000005)"));
+}
+
+#[test]
+fn test_formatter_cache() {
+    let calls = || FORMAT_CALLS.with(|c| c.get());
+    let formatter = MessageFormatter::new(vec![0x0409]);
+    let first = formatter.format(Error::with_code(5));
+    let before = calls();
+    assert_eq!(formatter.format(Error::with_code(5)), first);
+    assert_eq!(calls(), before);
+    formatter.format(Error::with_code(6));
+    assert!(calls() > before);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_formatter_languages() {
+    let err = Error::with_code(5);
+    // Inuktitut (Canada) is unlikely to be installed, so English is used.
+    let formatter = MessageFormatter::new(vec![0x085D, 0x0409]);
+    assert_eq!(formatter.languages(), &[0x085D, 0x0409]);
+    assert_eq!(formatter.format(err).as_deref(), Some("Access is denied."));
+    let expected = err.message_first_available(&[0x085D, 0x0409]);
+    assert_eq!(formatter.format(err).as_deref(), expected.as_deref());
+    assert_eq!(formatter.format(Error::with_code(15999)), None);
+}
+
+#[test]
+fn test_as_u16() {
+    assert_eq!(Error::with_code(5).as_u16(), Some(5));
+    assert_eq!(Error::with_code(0xFFFF).as_u16(), Some(0xFFFF));
+    assert_eq!(Error::with_code(0x10000).as_u16(), None);
+}
+
+#[test]
+fn test_code_nonzero() {
+    assert_eq!(Error::success().code_nonzero(), None);
+    assert_eq!(Error::with_code(5).code_nonzero(), NonZeroU32::new(5));
+    assert_eq!(std::mem::size_of::<Option<NonZeroU32>>(), 4);
+}
+
+#[test]
+fn test_lazy() {
+    let calls = || FORMAT_CALLS.with(|c| c.get());
+    let before = calls();
+    let lazy = Error::with_code(5).lazy();
+    assert_eq!(calls(), before);
+    assert_eq!(lazy.error(), Error::with_code(5));
+    assert_eq!(lazy.language(), default_language());
+    let text = lazy.to_string();
+    assert!(calls() > before);
+    #[cfg(windows)]
+    assert_eq!(text, "Access is denied.");
+    #[cfg(not(windows))]
+    assert!(text.starts_with("Error code 5 "));
+}
+
+#[test]
+fn test_raw_os_error() {
+    let err = Error::with_code(5);
+    assert_eq!(err.raw_os_error(), Some(err.code() as i32));
+    assert_eq!(
+        err.raw_os_error(),
+        io::Error::from_raw_os_error(5).raw_os_error()
+    );
+    assert_eq!(Error::success().raw_os_error(), Some(0));
+}
+
+#[test]
+fn test_eq_canonical() {
+    let file = Error::with_code(2);
+    let path = Error::with_code(3);
+    assert_ne!(file, path);
+    assert!(file.eq_canonical(path));
+    assert!(path.eq_canonical(file));
+    assert!(Error::with_code(39).eq_canonical(Error::with_code(112)));
+    assert!(file.eq_canonical(file));
+    assert!(!file.eq_canonical(Error::with_code(5)));
+}
+
+#[test]
+fn test_matches() {
+    const ERROR_ACCESS_DENIED: u32 = 5;
+    const E_ACCESSDENIED: i32 = 0x8007_0005_u32 as i32;
+    const E_POINTER: i32 = 0x8000_4003_u32 as i32;
+    let err = Error::with_code(5);
+    assert!(err.matches_dword(ERROR_ACCESS_DENIED));
+    assert!(!err.matches_dword(6));
+    assert!(err.matches_hresult(E_ACCESSDENIED));
+    assert!(!err.matches_hresult(E_POINTER));
+    let err = Error::with_code(E_POINTER as u32);
+    assert!(err.matches_hresult(E_POINTER));
+    assert!(!err.matches_hresult(E_ACCESSDENIED));
+    assert!(Error::success().matches_hresult(0));
+}
+
+#[test]
+fn test_is_not_supported() {
+    assert!(Error::with_code(50).is_not_supported());
+    assert!(Error::with_code(120).is_not_supported());
+    assert!(Error::with_code(1).is_not_supported());
+    assert!(!Error::with_code(5).is_not_supported());
+}
+
+#[test]
+fn test_needs_elevation() {
+    assert!(Error::with_code(5).needs_elevation());
+    assert!(Error::with_code(1314).needs_elevation());
+    assert!(Error::with_code(740).needs_elevation());
+    assert!(!Error::with_code(2).needs_elevation());
+    assert!(!Error::success().needs_elevation());
+}
+
+#[test]
+fn test_guidance() {
+    assert_eq!(
+        Error::with_code(5).guidance(),
+        Some("Try running as administrator.")
+    );
+    assert!(Error::with_code(1314)
+        .guidance()
+        .unwrap()
+        .contains("privilege"));
+    assert_eq!(Error::with_code(997).guidance(), None);
+    assert_eq!(Error::success().guidance(), None);
+}
+
+#[test]
+fn test_is_disk_full() {
+    assert!(Error::with_code(112).is_disk_full());
+    assert!(Error::with_code(39).is_disk_full());
+    assert!(!Error::with_code(1816).is_disk_full());
+    assert!(!Error::with_code(5).is_disk_full());
+    assert!(Error::with_code(112).is_storage_error());
+    assert!(Error::with_code(39).is_storage_error());
+    assert!(Error::with_code(1816).is_storage_error());
+    assert!(Error::with_code(19).is_storage_error());
+    assert!(!Error::with_code(5).is_storage_error());
+}
+
+#[test]
+fn test_is_cancelled() {
+    assert!(Error::with_code(995).is_cancelled());
+    assert!(Error::with_code(1223).is_cancelled());
+    assert!(!Error::with_code(5).is_cancelled());
+}
+
+#[test]
+fn test_is_handle_error() {
+    assert!(Error::with_code(6).is_handle_error());
+    assert!(Error::with_code(114).is_handle_error());
+    assert!(!Error::with_code(5).is_handle_error());
+}
+
+#[test]
+fn test_with_win32_code() {
+    assert_eq!(Error::with_win32_code(5), Ok(Error::with_code(5)));
+    assert_eq!(
+        Error::with_win32_code(0x8007_0005),
+        Err(Error::with_code(0x8007_0005))
+    );
+}
+
+#[test]
+fn test_is_auth_error() {
+    assert!(Error::with_code(1326).is_auth_error());
+    assert!(Error::with_code(1330).is_auth_error());
+    assert!(!Error::with_code(5).is_auth_error());
+}
+
+#[test]
+fn test_suggested_backoff() {
+    assert_eq!(
+        Error::with_code(32).suggested_backoff(),
+        Some(Duration::from_millis(100))
+    );
+    assert!(Error::with_code(170).suggested_backoff().is_some());
+    assert_eq!(Error::with_code(5).suggested_backoff(), None);
+}
+
+#[test]
+fn test_is_timeout() {
+    assert!(Error::with_code(1460).is_timeout());
+    assert!(Error::with_code(121).is_timeout());
+    assert!(Error::with_code(258).is_timeout());
+    assert!(Error::with_code(10060).is_timeout());
+    assert!(!Error::with_code(5).is_timeout());
+}
+
+#[test]
+fn test_is_pipe_error() {
+    assert!(Error::with_code(231).is_pipe_error());
+    assert!(Error::with_code(109).is_pipe_error());
+    assert!(Error::with_code(535).is_pipe_error());
+    assert!(!Error::with_code(5).is_pipe_error());
+    assert!(Error::with_code(231).is_pipe_retryable());
+    assert!(!Error::with_code(109).is_pipe_retryable());
+}
+
+#[test]
+fn test_is_buffer_too_small() {
+    assert!(Error::with_code(122).is_buffer_too_small());
+    assert!(Error::with_code(234).is_buffer_too_small());
+    assert!(!Error::with_code(5).is_buffer_too_small());
+}
+
+#[test]
+fn test_is_already_exists() {
+    assert!(Error::with_code(183).is_already_exists());
+    assert!(!Error::with_code(80).is_already_exists());
+}
+
+#[test]
+fn test_is_device_error() {
+    assert!(Error::with_code(21).is_device_error());
+    assert!(Error::with_code(55).is_device_error());
+    assert!(Error::with_code(1117).is_device_error());
+    assert!(!Error::with_code(5).is_device_error());
+}
+
+#[test]
+fn test_is_io_pending_more_data() {
+    assert!(Error::with_code(997).is_io_pending());
+    assert!(!Error::with_code(997).is_more_data());
+    assert!(Error::with_code(234).is_more_data());
+    assert!(!Error::with_code(234).is_io_pending());
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_first_available() {
+    let err = Error::with_code(1);
+    // Inuktitut (Canada) is unlikely to be installed.
+    assert_eq!(
+        err.message_first_available(&[0x045D]).as_deref(),
+        Some("Incorrect function.")
+    );
+    assert_eq!(err.message_first_available(&[]).as_deref(), Some("Incorrect function."));
+    assert_eq!(Error::with_code(15999).message_first_available(&[0x0409]), None);
+}
+
+#[cfg(all(windows, feature = "intern"))]
+#[test]
+fn test_message_arc() {
+    let a = Error::with_code(5).message_arc().unwrap();
+    let b = Error::with_code(5).message_arc().unwrap();
+    assert_eq!(&*a, "Access is denied.");
+    assert!(Arc::ptr_eq(&a, &b));
+    assert_eq!(Error::with_code(15999).message_arc(), None);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn test_message_cached() {
+    let calls = || FORMAT_CALLS.with(|c| c.get());
+    let err = Error::with_code(5);
+    let first = err.message_cached();
+    let before = calls();
+    assert_eq!(err.message_cached(), first);
+    assert_eq!(calls(), before);
+    #[cfg(windows)]
+    assert_eq!(first.as_deref(), Some("Access is denied."));
+    set_default_language(0);
+    assert_eq!(err.message_cached(), first);
+    assert!(calls() > before);
+}
+
+#[cfg(all(windows, feature = "intern"))]
+#[test]
+fn test_message_leaked() {
+    let a = Error::with_code(5).message_leaked().unwrap();
+    let b = Error::with_code(5).message_leaked().unwrap();
+    assert_eq!(a, "Access is denied.");
+    assert!(std::ptr::eq(a, b));
+    assert_eq!(Error::with_code(15999).message_leaked(), None);
+}
+
+#[cfg(feature = "arrayvec")]
+#[test]
+fn test_format_into_array() {
+    let mut out = arrayvec::ArrayString::<64>::new();
+    assert_eq!(Error::with_code(15999).format_into_array(&mut out), Ok(()));
+    assert!(out.is_empty());
+    #[cfg(windows)]
+    {
+        Error::with_code(5).format_into_array(&mut out).unwrap();
+        assert_eq!(out.as_str(), "Access is denied.");
+        let mut small = arrayvec::ArrayString::<8>::new();
+        small.push_str("x");
+        assert!(Error::with_code(5).format_into_array(&mut small).is_err());
+        assert_eq!(small.as_str(), "x");
+    }
+}
+
+#[test]
+fn test_trim_utf16() {
+    let units: Vec<u16> = " \tAccess is denied.\r\n".encode_utf16().collect();
+    let trimmed: Vec<u16> = "Access is denied.".encode_utf16().collect();
+    assert_eq!(trim_utf16(&units), &trimmed[..]);
+    assert_eq!(trim_utf16(&[0x20, 0x0D, 0x0A]), &[] as &[u16]);
+    // A lone surrogate is not whitespace.
+    assert_eq!(trim_utf16(&[0xD83D, 0x20]), &[0xD83D]);
+}
+
+#[test]
+fn test_truncate_utf16() {
+    // "ab" followed by U+1F600, a surrogate pair, then "c".
+    let units: Vec<u16> = "ab\u{1F600}c".encode_utf16().collect();
+    assert_eq!(units.len(), 5);
+    assert_eq!(truncate_utf16(&units, 3), &units[..2]);
+    assert_eq!(truncate_utf16(&units, 4), &units[..4]);
+    assert_eq!(truncate_utf16(&units, 2), &units[..2]);
+    assert_eq!(truncate_utf16(&units, 0), &[] as &[u16]);
+    assert_eq!(truncate_utf16(&units, 9), &units[..]);
+    assert!(String::from_utf16(truncate_utf16(&units, 3)).is_ok());
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_utf16_truncated() {
+    let units = Error::with_code(5).message_utf16_truncated(6).unwrap();
+    assert_eq!(String::from_utf16(&units).unwrap(), "Access");
+    assert_eq!(Error::with_code(15999).message_utf16_truncated(6), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_utf8() {
+    let bytes = Error::with_code(1).message_utf8().unwrap();
+    assert_eq!(bytes, b"Incorrect function.".to_vec());
+    assert_eq!(Error::with_code(15999).message_utf8(), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_en_us() {
+    assert!(Error::with_code(0).message_en_us().is_some());
+    assert_eq!(Error::with_code(15999).message_en_us(), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_invariant() {
+    // The exact text depends on which language resources are installed.
+    assert!(Error::with_code(5).message_invariant().is_some());
+    assert_eq!(Error::with_code(15999).message_invariant(), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_or() {
+    let err = Error::with_code(1);
+    assert_eq!(err.message_or("unknown"), "Incorrect function.");
+    let err = Error::with_code(15999);
+    assert_eq!(err.message_or("unknown"), "unknown");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_from_module_named() {
+    // NERR_NetNotStarted, defined in netmsg.dll
+    let err = Error::with_code(2102);
+    assert_eq!(
+        err.message_from_module_named("netmsg.dll").as_deref(),
+        Some("The workstation driver is not installed.")
+    );
+    assert_eq!(err.message_from_module_named("not-a-real-module.dll"), None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_message_from_current_module() {
+    // The test executable has no message table.
+    assert_eq!(Error::with_code(0x2000_0001).message_from_current_module(), None);
+}
+
+#[test]
+fn test_fmt_concurrent() {
+    let codes = [0, 1, 2, 5, 560, 6719, 15999];
+    let expected: Vec<Option<String>> = codes.iter().map(|&c| fmt_error_lang(c, 0)).collect();
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            std::thread::spawn(move || {
+                (0..50)
+                    .map(|_| {
+                        codes
+                            .iter()
+                            .map(|&c| fmt_error_lang(c, 0))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    for thread in threads {
+        for run in thread.join().unwrap() {
+            assert_eq!(run, expected);
+        }
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn test_fmt_error_longest() {
+    let s = fmt_error(6719).unwrap();
+    assert!(s.encode_utf16().count() <= BUF_SIZE);
+    assert!(s.trim_end().ends_with("go offline."));
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_fmt_error_bounds(code in proptest::prelude::any::<u32>()) {
+        if let Some(s) = fmt_error(code) {
+            proptest::prop_assert!(!s.is_empty());
+            proptest::prop_assert!(s.encode_utf16().count() <= BUF_SIZE);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_stub() {
+    let err = last_error();
+    assert_eq!(err.code(), 0);
+    let err = Error::with_code(5);
+    assert_eq!(err.message(), None);
+    assert_eq!(err.message_or_code(), "5");
+    assert_eq!(err.to_full_string(), "ERROR_ACCESS_DENIED (0x00000005): Unknown error");
+    assert_eq!(format!("{:?}", err), "Error { code: 5, message: None }");
+    assert_eq!(
+        format!("{}", err),
+        "Error code 5 (could not format due to internal error code: 0)"
+    );
+    assert_eq!(Error::from_wait_result(0xFFFF_FFFF), Some(Error::success()));
+    assert_eq!(os_version(), (0, 0, 0));
 }
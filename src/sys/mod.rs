@@ -0,0 +1,82 @@
+//! The platform layer: the handful of Windows API items the crate uses.
+//!
+//! On Windows these are re-exported from winapi. Everywhere else a stub with
+//! the same names is used, so the crate still builds for type-checking and docs.
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+pub(crate) use self::windows::*;
+
+#[cfg(not(windows))]
+mod stub;
+#[cfg(not(windows))]
+pub(crate) use self::stub::*;
+
+// Error codes from winerror.h. These are plain numbers, the same on every
+// platform, so they are shared rather than taken from winapi.
+pub(crate) const ERROR_INVALID_FUNCTION: u32 = 1;
+pub(crate) const ERROR_FILE_NOT_FOUND: u32 = 2;
+pub(crate) const ERROR_PATH_NOT_FOUND: u32 = 3;
+pub(crate) const ERROR_TOO_MANY_OPEN_FILES: u32 = 4;
+pub(crate) const ERROR_ACCESS_DENIED: u32 = 5;
+pub(crate) const ERROR_INVALID_HANDLE: u32 = 6;
+pub(crate) const ERROR_ARENA_TRASHED: u32 = 7;
+pub(crate) const ERROR_NOT_ENOUGH_MEMORY: u32 = 8;
+pub(crate) const ERROR_OUTOFMEMORY: u32 = 14;
+pub(crate) const ERROR_WRITE_PROTECT: u32 = 19;
+pub(crate) const ERROR_BAD_UNIT: u32 = 20;
+pub(crate) const ERROR_NOT_READY: u32 = 21;
+pub(crate) const ERROR_CRC: u32 = 23;
+pub(crate) const ERROR_SECTOR_NOT_FOUND: u32 = 27;
+pub(crate) const ERROR_WRITE_FAULT: u32 = 29;
+pub(crate) const ERROR_READ_FAULT: u32 = 30;
+pub(crate) const ERROR_GEN_FAILURE: u32 = 31;
+pub(crate) const ERROR_SHARING_VIOLATION: u32 = 32;
+pub(crate) const ERROR_LOCK_VIOLATION: u32 = 33;
+pub(crate) const ERROR_HANDLE_DISK_FULL: u32 = 39;
+pub(crate) const ERROR_NOT_SUPPORTED: u32 = 50;
+pub(crate) const ERROR_DEV_NOT_EXIST: u32 = 55;
+pub(crate) const ERROR_FILE_EXISTS: u32 = 80;
+pub(crate) const ERROR_INVALID_PARAMETER: u32 = 87;
+pub(crate) const ERROR_BROKEN_PIPE: u32 = 109;
+pub(crate) const ERROR_DISK_FULL: u32 = 112;
+pub(crate) const ERROR_INVALID_TARGET_HANDLE: u32 = 114;
+pub(crate) const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+pub(crate) const ERROR_SEM_TIMEOUT: u32 = 121;
+pub(crate) const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+pub(crate) const ERROR_BUSY: u32 = 170;
+pub(crate) const ERROR_ALREADY_EXISTS: u32 = 183;
+pub(crate) const ERROR_PIPE_BUSY: u32 = 231;
+pub(crate) const ERROR_NO_DATA: u32 = 232;
+pub(crate) const ERROR_PIPE_NOT_CONNECTED: u32 = 233;
+pub(crate) const ERROR_MORE_DATA: u32 = 234;
+pub(crate) const WAIT_TIMEOUT: u32 = 258;
+#[cfg(test)]
+pub(crate) const ERROR_MR_MID_NOT_FOUND: u32 = 317;
+pub(crate) const ERROR_PIPE_CONNECTED: u32 = 535;
+pub(crate) const ERROR_ELEVATION_REQUIRED: u32 = 740;
+pub(crate) const ERROR_OPERATION_ABORTED: u32 = 995;
+pub(crate) const ERROR_IO_PENDING: u32 = 997;
+pub(crate) const ERROR_MEDIA_CHANGED: u32 = 1110;
+pub(crate) const ERROR_NO_MEDIA_IN_DRIVE: u32 = 1112;
+pub(crate) const ERROR_IO_DEVICE: u32 = 1117;
+pub(crate) const ERROR_CANCELLED: u32 = 1223;
+pub(crate) const ERROR_DISK_QUOTA_EXCEEDED: u32 = 1295;
+pub(crate) const ERROR_PRIVILEGE_NOT_HELD: u32 = 1314;
+pub(crate) const ERROR_NO_SUCH_USER: u32 = 1317;
+pub(crate) const ERROR_LOGON_FAILURE: u32 = 1326;
+pub(crate) const ERROR_PASSWORD_EXPIRED: u32 = 1330;
+pub(crate) const ERROR_ACCOUNT_DISABLED: u32 = 1331;
+pub(crate) const ERROR_TIMEOUT: u32 = 1460;
+pub(crate) const ERROR_NOT_ENOUGH_QUOTA: u32 = 1816;
+pub(crate) const ERROR_ACCOUNT_LOCKED_OUT: u32 = 1909;
+pub(crate) const WSAEACCES: u32 = 10013;
+pub(crate) const WSAEWOULDBLOCK: u32 = 10035;
+pub(crate) const WSAEADDRINUSE: u32 = 10048;
+pub(crate) const WSAEADDRNOTAVAIL: u32 = 10049;
+pub(crate) const WSAECONNABORTED: u32 = 10053;
+pub(crate) const WSAECONNRESET: u32 = 10054;
+pub(crate) const WSAENOTCONN: u32 = 10057;
+pub(crate) const WSAETIMEDOUT: u32 = 10060;
+pub(crate) const WSAECONNREFUSED: u32 = 10061;
@@ -0,0 +1,83 @@
+//! A stand-in for the Windows API on other platforms. Every call fails or
+//! returns a fixed value: there is no last error (it is always 0), and no
+//! message ever formats.
+#![allow(non_snake_case, clippy::upper_case_acronyms)]
+
+use std::ffi::c_void;
+
+pub(crate) type BOOL = i32;
+pub(crate) type HANDLE = *mut c_void;
+// An opaque pointee for HMODULE, as in winapi, so that public signatures taking
+// a module handle have the same shape on every platform. It is `pub` because it
+// appears in those signatures.
+pub enum HINSTANCE__ {}
+pub(crate) type HMODULE = *mut HINSTANCE__;
+pub(crate) type LPCVOID = *const c_void;
+
+pub(crate) const NULL: *mut c_void = std::ptr::null_mut();
+pub(crate) const INVALID_HANDLE_VALUE: HANDLE = -1isize as HANDLE;
+
+pub(crate) const FORMAT_MESSAGE_ALLOCATE_BUFFER: u32 = 0x0000_0100;
+pub(crate) const FORMAT_MESSAGE_IGNORE_INSERTS: u32 = 0x0000_0200;
+pub(crate) const FORMAT_MESSAGE_FROM_HMODULE: u32 = 0x0000_0800;
+pub(crate) const FORMAT_MESSAGE_FROM_SYSTEM: u32 = 0x0000_1000;
+pub(crate) const FORMAT_MESSAGE_ARGUMENT_ARRAY: u32 = 0x0000_2000;
+pub(crate) const FORMAT_MESSAGE_MAX_WIDTH_MASK: u32 = 0x0000_00FF;
+pub(crate) const LOAD_LIBRARY_AS_DATAFILE: u32 = 0x0000_0002;
+
+pub(crate) const WAIT_OBJECT_0: u32 = 0;
+pub(crate) const WAIT_ABANDONED: u32 = 0x80;
+pub(crate) const WAIT_FAILED: u32 = 0xFFFF_FFFF;
+
+#[derive(Clone, Copy)]
+pub(crate) struct OSVERSIONINFOW {
+    pub dwOSVersionInfoSize: u32,
+    pub dwMajorVersion: u32,
+    pub dwMinorVersion: u32,
+    pub dwBuildNumber: u32,
+}
+
+pub(crate) unsafe fn GetLastError() -> u32 {
+    0
+}
+pub(crate) unsafe fn SetLastError(_code: u32) {}
+
+pub(crate) unsafe fn FormatMessageW(
+    _flags: u32,
+    _source: LPCVOID,
+    _message_id: u32,
+    _language_id: u32,
+    _buffer: *mut u16,
+    _size: u32,
+    _arguments: *mut c_void,
+) -> u32 {
+    0
+}
+pub(crate) unsafe fn LocalFree(_mem: HANDLE) -> HANDLE {
+    NULL
+}
+
+pub(crate) unsafe fn LoadLibraryExW(_name: *const u16, _file: HANDLE, _flags: u32) -> HMODULE {
+    std::ptr::null_mut()
+}
+pub(crate) unsafe fn FreeLibrary(_module: HMODULE) -> BOOL {
+    0
+}
+pub(crate) unsafe fn GetModuleHandleW(_name: *const u16) -> HMODULE {
+    std::ptr::null_mut()
+}
+
+pub(crate) unsafe fn GetUserDefaultUILanguage() -> u16 {
+    0
+}
+
+pub(crate) unsafe fn RtlGetVersion(_info: *mut OSVERSIONINFOW) -> i32 {
+    // STATUS_NOT_IMPLEMENTED
+    0xC000_0002_u32 as i32
+}
+
+#[cfg(feature = "crt")]
+pub(crate) unsafe fn _wcserror_s(_buffer: *mut u16, _size: usize, _errnum: i32) -> i32 {
+    // EINVAL
+    22
+}
@@ -0,0 +1,29 @@
+pub(crate) use winapi::{
+    shared::{
+        minwindef::{BOOL, HMODULE, LPCVOID},
+        ntdef::{HANDLE, NULL},
+    },
+    um::{
+        errhandlingapi::{GetLastError, SetLastError},
+        handleapi::INVALID_HANDLE_VALUE,
+        libloaderapi::{FreeLibrary, GetModuleHandleW, LoadLibraryExW, LOAD_LIBRARY_AS_DATAFILE},
+        winbase::{
+            FormatMessageW, LocalFree, FORMAT_MESSAGE_ALLOCATE_BUFFER,
+            FORMAT_MESSAGE_ARGUMENT_ARRAY, FORMAT_MESSAGE_FROM_HMODULE, FORMAT_MESSAGE_FROM_SYSTEM,
+            FORMAT_MESSAGE_IGNORE_INSERTS, FORMAT_MESSAGE_MAX_WIDTH_MASK, WAIT_ABANDONED,
+            WAIT_FAILED, WAIT_OBJECT_0,
+        },
+        winnls::GetUserDefaultUILanguage,
+        winnt::OSVERSIONINFOW,
+    },
+};
+
+#[link(name = "ntdll")]
+extern "system" {
+    pub(crate) fn RtlGetVersion(info: *mut OSVERSIONINFOW) -> i32;
+}
+
+#[cfg(feature = "crt")]
+extern "C" {
+    pub(crate) fn _wcserror_s(buffer: *mut u16, size: usize, errnum: i32) -> i32;
+}
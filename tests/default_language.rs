@@ -0,0 +1,12 @@
+//! Installing a default language changes formatting for the whole process, so
+//! this runs in its own test binary instead of alongside the unit tests.
+
+use winerr::{default_language, use_user_default_language, user_default_language};
+
+#[test]
+fn test_use_user_default_language() {
+    use_user_default_language();
+    assert_eq!(default_language(), user_default_language());
+    #[cfg(windows)]
+    assert_ne!(default_language(), 0);
+}
@@ -0,0 +1,40 @@
+//! Registering a message module changes formatting for the whole process, so
+//! this runs in its own test binary instead of alongside the unit tests.
+#![cfg(windows)]
+
+use winapi::um::libloaderapi::{LoadLibraryExW, LOAD_LIBRARY_AS_DATAFILE};
+use winerr::{register_message_module, Error, MessageFormatter};
+
+#[test]
+fn test_register_message_module() {
+    let name: Vec<u16> = "netmsg.dll"
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let module = unsafe {
+        LoadLibraryExW(
+            name.as_ptr(),
+            std::ptr::null_mut(),
+            LOAD_LIBRARY_AS_DATAFILE,
+        )
+    };
+    assert!(!module.is_null());
+    // NERR_NetNotStarted, defined in netmsg.dll
+    let err = Error::with_code(2102);
+    assert_eq!(err.message(), None);
+    let formatter = MessageFormatter::new(vec![]);
+    assert_eq!(formatter.format(err), None);
+    register_message_module(module);
+    assert_eq!(
+        formatter.format(err).as_deref(),
+        Some("The workstation driver is not installed.")
+    );
+    assert_eq!(
+        err.message().as_deref(),
+        Some("The workstation driver is not installed.")
+    );
+    assert_eq!(
+        format!("{}", err),
+        "The workstation driver is not installed."
+    );
+}